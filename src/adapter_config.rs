@@ -0,0 +1,114 @@
+use crate::{CellPacking, WorldBackend};
+
+/// Configuration picked from what the adapter supports, rather than assuming every feature exists
+#[derive(Clone, Debug)]
+pub struct AdapterConfig {
+    pub adapter: wgpu::AdapterInfo,
+    pub world_backend: WorldBackend,
+    /// Whether GPU timestamps are available for profiling
+    pub timestamps: bool,
+    /// Largest side of a single world tile
+    pub max_tile_size: u32,
+    /// Whether `Rgba32Uint` storage textures can be read, which [`CellPacking::Vector`] needs in a
+    /// texture array world
+    pub vector_textures: bool,
+    /// Anything which had to be disabled or changed, for displaying to the user
+    pub notes: Vec<String>,
+}
+
+impl AdapterConfig {
+    pub fn negotiate(adapter: &wgpu::Adapter, requested_backend: Option<WorldBackend>) -> Self {
+        let features = adapter.features();
+        let mut notes = Vec::new();
+
+        let best_backend = WorldBackend::select(features);
+        let world_backend = match requested_backend {
+            Some(backend) if features.contains(backend.required_features()) => backend,
+            Some(backend) => {
+                notes.push(format!("{backend:?} world unsupported, using {best_backend:?}"));
+                best_backend
+            },
+            None => best_backend,
+        };
+
+        if best_backend == WorldBackend::StorageBuffer {
+            notes.push(format!(
+                "Texture world unavailable, missing {:?}",
+                WorldBackend::TEXTURE_ARRAY_FEATURES.difference(features)
+            ));
+        }
+
+        let vector_textures = adapter
+            .get_texture_format_features(CellPacking::Vector.texture_format())
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::STORAGE_READ_WRITE);
+        if world_backend == WorldBackend::TextureArray && !vector_textures {
+            notes.push("Wide packing unavailable, Rgba32Uint storage textures can't be read".into());
+        }
+
+        let timestamps = features.contains(wgpu::Features::TIMESTAMP_QUERY);
+        if !timestamps {
+            notes.push("Profiler disabled (no TIMESTAMP_QUERY)".into());
+        }
+
+        Self {
+            adapter: adapter.get_info(),
+            world_backend,
+            timestamps,
+            max_tile_size: adapter.limits().max_texture_dimension_2d,
+            vector_textures,
+            notes,
+        }
+    }
+
+    /// `requested`, or [`CellPacking::Scalar`] when the world can't be packed that way
+    pub fn packing(&self, requested: CellPacking) -> CellPacking {
+        match requested {
+            CellPacking::Vector if self.world_backend == WorldBackend::TextureArray && !self.vector_textures => CellPacking::Scalar,
+            packing => packing,
+        }
+    }
+
+    pub fn required_features(&self) -> wgpu::Features {
+        let mut features = self.world_backend.required_features();
+        if self.timestamps {
+            features |= wgpu::Features::TIMESTAMP_QUERY;
+        }
+        features
+    }
+
+    /// Opens a device with the negotiated features and limits, failing with whichever of them the
+    /// adapter refused
+    pub async fn request_device(&self, adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue, wgpu::Limits), String> {
+        let limits = self.required_limits(adapter);
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features: self.required_features(),
+                    required_limits: limits.clone(),
+                    label: None,
+                    memory_hints: wgpu::MemoryHints::default(),
+                },
+                None,
+            )
+            .await
+            .map_err(|err| format!("{} refused the device: {err}", self.adapter.name))?;
+
+        Ok((device, queue, limits))
+    }
+
+    /// Starts from limits every adapter can provide, raising only those the world needs
+    pub fn required_limits(&self, adapter: &wgpu::Adapter) -> wgpu::Limits {
+        let adapter_limits = adapter.limits();
+
+        wgpu::Limits {
+            max_texture_dimension_2d: self.max_tile_size,
+            max_storage_buffer_binding_size: adapter_limits.max_storage_buffer_binding_size,
+            max_storage_buffers_per_shader_stage: adapter_limits.max_storage_buffers_per_shader_stage,
+            max_storage_textures_per_shader_stage: adapter_limits.max_storage_textures_per_shader_stage,
+            max_buffer_size: adapter_limits.max_buffer_size,
+            ..wgpu::Limits::downlevel_defaults()
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use glam::{ivec2, IVec2};
+
+use crate::{census::{self, ObjectKind}, pattern::Pattern, rule::Rule};
+
+/// Digits of the extended Wechsler format. Columns use the first 32, runs of empty columns after a
+/// `y` use all of them
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+/// Rows in each strip, the top row being the least significant bit of a column
+const STRIP_HEIGHT: i32 = 5;
+
+/// The eight rotations and reflections of the square
+const SYMMETRIES: [fn(IVec2) -> IVec2; 8] = [
+    |c| c,
+    |c| ivec2(-c.y, c.x),
+    |c| ivec2(-c.x, -c.y),
+    |c| ivec2(c.y, -c.x),
+    |c| ivec2(-c.x, c.y),
+    |c| ivec2(c.x, -c.y),
+    |c| ivec2(c.y, c.x),
+    |c| ivec2(-c.y, -c.x),
+];
+
+#[derive(Debug)]
+pub enum ApgcodeError {
+    /// Didn't start with `xs`, `xp` or `xq`, a number and an underscore
+    Prefix,
+    Character(char),
+}
+
+impl fmt::Display for ApgcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApgcodeError::Prefix => write!(f, "expected a prefix such as 'xs4_'"),
+            ApgcodeError::Character(c) => write!(f, "unexpected '{c}'"),
+        }
+    }
+}
+
+impl std::error::Error for ApgcodeError {}
+
+/// Runs a pattern on its own to classify it and gives its canonical apgcode, such as `xs4_33` for
+/// the block. `None` if it didn't repeat within `max_period` generations
+pub fn encode(pattern: &Pattern, rule: Rule, max_period: u32) -> Option<String> {
+    let cells = pattern.cells.iter().map(|cell| cell.as_ivec2()).collect::<Vec<_>>();
+    let (kind, phases) = census::classify(&cells, rule, max_period);
+    canonical(kind, &phases)
+}
+
+/// Apgcode of an object which has already been classified. The shortest, then alphabetically
+/// first, encoding of any phase in any orientation is used, so it doesn't matter how it was found
+pub fn canonical(kind: ObjectKind, phases: &[Vec<IVec2>]) -> Option<String> {
+    let prefix = match kind {
+        ObjectKind::StillLife => format!("xs{}", phases.first()?.len()),
+        ObjectKind::Oscillator { period } => format!("xp{period}"),
+        ObjectKind::Spaceship { period, .. } => format!("xq{period}"),
+        ObjectKind::Unclassified => return None,
+    };
+
+    let body = phases
+        .iter()
+        .flat_map(|phase| SYMMETRIES.map(|transform| wechsler(&census::normalise(phase.iter().copied().map(transform)).0)))
+        .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))?;
+
+    Some(format!("{prefix}_{body}"))
+}
+
+/// Extended Wechsler format of cells whose bounding box starts at the origin. Rows are split into
+/// strips of five separated by `z`, and each strip is written a column at a time
+pub fn wechsler(cells: &[IVec2]) -> String {
+    let size = cells.iter().map(|&cell| cell + 1).fold(IVec2::ZERO, IVec2::max).as_uvec2();
+    let mut strips = vec![vec![0; size.x as usize]; size.y.div_ceil(STRIP_HEIGHT as u32) as usize];
+    for cell in cells {
+        strips[(cell.y / STRIP_HEIGHT) as usize][cell.x as usize] |= 1 << (cell.y % STRIP_HEIGHT);
+    }
+
+    let mut code = String::new();
+    for (i, strip) in strips.iter().enumerate() {
+        if i > 0 {
+            code.push('z');
+        }
+
+        // Empty columns are only written once something follows them
+        let mut zeros = 0;
+        for &column in strip {
+            if column == 0 {
+                zeros += 1;
+                continue;
+            }
+
+            push_zeros(&mut code, zeros);
+            zeros = 0;
+            code.push(DIGITS[column] as char);
+        }
+    }
+
+    code
+}
+
+/// Writes a run of empty columns as compactly as the format allows
+fn push_zeros(code: &mut String, mut zeros: usize) {
+    while zeros > 39 {
+        code.push_str("yz");
+        zeros -= 39;
+    }
+
+    match zeros {
+        0 => {},
+        1 => code.push('0'),
+        2 => code.push('w'),
+        3 => code.push('x'),
+        _ => {
+            code.push('y');
+            code.push(DIGITS[zeros - 4] as char);
+        },
+    }
+}
+
+/// The phase of the object an apgcode was made from, moved to the origin
+pub fn decode(code: &str) -> Result<Pattern, ApgcodeError> {
+    let (prefix, body) = code.trim().split_once('_').ok_or(ApgcodeError::Prefix)?;
+    let valid_prefix = ["xs", "xp", "xq"]
+        .iter()
+        .filter_map(|kind| prefix.strip_prefix(kind))
+        .any(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()));
+    if !valid_prefix {
+        return Err(ApgcodeError::Prefix);
+    }
+
+    let digit = |c: char| DIGITS.iter().position(|&d| d as char == c);
+
+    let mut cells = Vec::new();
+    // Left-most column not yet read, on the top row of the current strip
+    let mut position = IVec2::ZERO;
+    let mut chars = body.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            'z' => position = ivec2(0, position.y + STRIP_HEIGHT),
+            'w' => position.x += 2,
+            'x' => position.x += 3,
+            'y' => {
+                let run = chars.next().and_then(digit).ok_or(ApgcodeError::Character('y'))?;
+                position.x += 4 + run as i32;
+            },
+            _ => {
+                let column = digit(c).filter(|&column| column < 32).ok_or(ApgcodeError::Character(c))?;
+                cells.extend((0..STRIP_HEIGHT)
+                    .filter(|row| column & (1 << row) != 0)
+                    .map(|row| position + ivec2(0, row)));
+                position.x += 1;
+            },
+        }
+    }
+
+    Ok(Pattern::from_cells(&census::normalise(cells.into_iter()).0, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn cells(coordinates: &[(i32, i32)]) -> Vec<IVec2> {
+        coordinates.iter().map(|&(x, y)| ivec2(x, y)).collect()
+    }
+
+    fn encode_cells(cells: &[IVec2]) -> Option<String> {
+        encode(&Pattern::from_cells(&census::normalise(cells.iter().copied()).0, None), Rule::default(), 16)
+    }
+
+    #[test]
+    fn block() {
+        assert_eq!(encode_cells(&cells(&[(0, 0), (1, 0), (0, 1), (1, 1)])).as_deref(), Some("xs4_33"));
+    }
+
+    #[test]
+    fn blinker() {
+        assert_eq!(encode_cells(&cells(&[(0, 0), (1, 0), (2, 0)])).as_deref(), Some("xp2_7"));
+        assert_eq!(encode_cells(&cells(&[(0, 0), (0, 1), (0, 2)])).as_deref(), Some("xp2_7"));
+    }
+
+    #[test]
+    fn glider_in_every_phase_and_orientation() {
+        let glider = cells(&[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        for transform in SYMMETRIES {
+            let mut phase = glider.iter().copied().map(transform).collect::<HashSet<_>>();
+            for _ in 0..4 {
+                assert_eq!(encode_cells(&phase.iter().copied().collect::<Vec<_>>()).as_deref(), Some("xq4_153"));
+                phase = census::step(&phase, Rule::default());
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for code in ["xs4_33", "xp2_7", "xq4_153", "xs6_696", "xp15_4r4z4r4"] {
+            let pattern = decode(code).unwrap();
+            assert_eq!(encode(&pattern, Rule::default(), 16).as_deref(), Some(code));
+        }
+    }
+
+    #[test]
+    fn round_trip_with_empty_columns() {
+        // Two blocks with a run of two empty columns, then a run of six
+        let cells = census::normalise(cells(&[(0, 0), (1, 0), (0, 1), (1, 1), (4, 0), (5, 0), (4, 1), (5, 1), (12, 5), (13, 5), (12, 6), (13, 6)]).into_iter()).0;
+        let code = wechsler(&cells);
+        assert_eq!(code, "33w33zy833");
+        assert_eq!(decode(&format!("xs12_{code}")).unwrap().cells, Pattern::from_cells(&cells, None).cells);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(decode("33"), Err(ApgcodeError::Prefix)));
+        assert!(matches!(decode("xs_33"), Err(ApgcodeError::Prefix)));
+        assert!(matches!(decode("xs4_3A"), Err(ApgcodeError::Character('A'))));
+    }
+}
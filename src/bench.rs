@@ -0,0 +1,180 @@
+use std::time::{Duration, Instant};
+
+use glam::{uvec2, UVec2};
+use serde::Serialize;
+
+use crate::{headless::HeadlessContext, rendering_utils::Profiler, simulation::Simulation, CellPacking, ProfileScope, Settings, WorldBackend};
+
+/// Generations run before timing starts, so that pipelines and caches are warm
+const WARMUP_GENERATIONS: u32 = 10;
+const GENERATIONS: u32 = 100;
+const SEED: u32 = 0x5eed;
+
+const SIZES: [u32; 3] = [4096, 16384, 65536];
+/// Software adapters are orders of magnitude slower, so they get smaller worlds
+const SOFTWARE_SIZES: [u32; 2] = [512, 2048];
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct BenchOptions {
+    /// Run on a fallback (CPU) adapter
+    pub software: bool,
+    pub world_backend: Option<WorldBackend>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BenchReport {
+    pub adapter: AdapterReport,
+    /// `gpu_timestamp` when the adapter supports timestamp queries, otherwise `cpu_wall`, which
+    /// includes submission and synchronisation overhead
+    pub timing_source: &'static str,
+    pub warmup_generations: u32,
+    pub generations: u32,
+    pub seed: u32,
+    pub runs: Vec<BenchRun>,
+    /// Anything which was disabled or skipped
+    pub notes: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdapterReport {
+    pub name: String,
+    pub backend: String,
+    pub device_type: String,
+    pub driver: String,
+    pub driver_info: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BenchRun {
+    pub world_size: [u32; 2],
+    pub packing: String,
+    pub world_backend: String,
+    /// Times per generation
+    pub mean_ms: f64,
+    pub median_ms: f64,
+    pub p99_ms: f64,
+    /// Billions of cells updated per second, from the mean
+    pub gcells_per_sec: f64,
+}
+
+/// Runs a fixed seeded soup over every world size and cell packing, timing each generation. Fails
+/// if the adapter refuses the device
+pub fn run(options: BenchOptions) -> Result<BenchReport, String> {
+    let context = pollster::block_on(HeadlessContext::new(options.software, options.world_backend))?;
+    let adapter_config = &context.adapter_config;
+    let world_backend = adapter_config.world_backend;
+    let mut notes = adapter_config.notes.clone();
+
+    let sizes: &[u32] = if options.software { &SOFTWARE_SIZES } else { &SIZES };
+
+    let mut runs = Vec::new();
+    for &size in sizes {
+        // The adapter's notes already say when a packing isn't available
+        for packing in [CellPacking::Scalar, CellPacking::Vector].into_iter().filter(|&packing| adapter_config.packing(packing) == packing) {
+            let world_size = uvec2(size, size);
+            if !world_backend.fits(packing, world_size, 1, &context.limits) {
+                notes.push(format!("Skipped {size}x{size} {packing:?}, too large for {world_backend:?}"));
+                continue;
+            }
+
+            let times = time_generations(&context, Settings {
+                packing,
+                world_backend: Some(world_backend),
+                world_size,
+                seed: SEED,
+                ..Default::default()
+            });
+            runs.push(BenchRun::new(world_size, packing, world_backend, times));
+        }
+    }
+
+    let info = &adapter_config.adapter;
+    Ok(BenchReport {
+        adapter: AdapterReport {
+            name: info.name.clone(),
+            backend: format!("{:?}", info.backend),
+            device_type: format!("{:?}", info.device_type),
+            driver: info.driver.clone(),
+            driver_info: info.driver_info.clone(),
+        },
+        timing_source: if adapter_config.timestamps { "gpu_timestamp" } else { "cpu_wall" },
+        warmup_generations: WARMUP_GENERATIONS,
+        generations: GENERATIONS,
+        seed: SEED,
+        runs,
+        notes,
+    })
+}
+
+/// Submits every generation on its own so that each one can be timed separately
+fn time_generations(context: &HeadlessContext, settings: Settings) -> Vec<Duration> {
+    let HeadlessContext { device, queue, limits, adapter_config } = context;
+
+    let mut simulation = Simulation::new(device, limits, adapter_config, &settings)
+        .expect("Only rule shaders can fail to compile");
+    simulation.init(device, queue);
+
+    let mut profiler = adapter_config.timestamps.then(|| Profiler::new(
+        &[ProfileScope::ComputeUpdate.label()],
+        GENERATIONS as usize,
+        device,
+        queue.get_timestamp_period()
+    ));
+
+    let mut wall_times = Vec::with_capacity(GENERATIONS as usize);
+
+    for generation in 0..WARMUP_GENERATIONS + GENERATIONS {
+        let timed = generation >= WARMUP_GENERATIONS;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Bench Encoder")
+        });
+        simulation.step(
+            &mut encoder,
+            profiler.as_ref().filter(|_| timed).map(|profiler| profiler.compute_pass_timestamp_writes(0))
+        );
+        if let Some(profiler) = &mut profiler {
+            profiler.resolve(&mut encoder);
+        }
+
+        let start = Instant::now();
+        queue.submit(std::iter::once(encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+        if timed {
+            wall_times.push(start.elapsed());
+        }
+
+        if let Some(profiler) = &mut profiler {
+            profiler.flush(device);
+        }
+    }
+
+    match profiler {
+        Some(profiler) => profiler.samples(0).collect(),
+        None => wall_times,
+    }
+}
+
+impl BenchRun {
+    fn new(world_size: UVec2, packing: CellPacking, world_backend: WorldBackend, mut times: Vec<Duration>) -> Self {
+        times.sort();
+        let ms = |time: Duration| time.as_secs_f64() * 1000.;
+
+        let mean = times.iter().sum::<Duration>().as_secs_f64() / times.len().max(1) as f64;
+        let percentile = |p: f64| times
+            .get(((times.len() as f64 * p).ceil() as usize).saturating_sub(1))
+            .copied()
+            .map(ms)
+            .unwrap_or_default();
+
+        Self {
+            world_size: world_size.to_array(),
+            packing: format!("{packing:?}"),
+            world_backend: format!("{world_backend:?}"),
+            mean_ms: mean * 1000.,
+            median_ms: percentile(0.5),
+            p99_ms: percentile(0.99),
+            gcells_per_sec: world_size.as_dvec2().element_product() / mean / 1e9,
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use std::{fs, hash::{DefaultHasher, Hasher}, path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use conway_wgpu::{census::{self, Census, SoupId}, cli::{parse_size, WorldArgs}, HeadlessContext, Pattern, RuleKind, Settings, Simulation};
+use glam::{IVec2, UVec2};
+use serde::Serialize;
+
+/// Generations recorded into a single command buffer
+const GENERATIONS_PER_SUBMIT: u32 = 256;
+/// Gliders and anything else which escapes a soup leave debris against the dead border of its
+/// tile, so objects with cells this close to the border are left out of the census
+const BORDER_MARGIN: u32 = 8;
+/// Soups in a search are checked for having settled every time this many generations have run
+const CHECK_INTERVAL: u32 = 60;
+/// Longest period a soup can repeat with and count as settled
+const MAX_SETTLED_PERIOD: u32 = 15;
+/// Generations read back at the end of every check, which is enough to see the longest period
+/// repeat
+const SETTLE_WINDOW: u32 = 2 * MAX_SETTLED_PERIOD;
+/// Objects this close to the border of a soup which hasn't settled are classified, and removed if
+/// they're spaceships, before they crash into it. Spaceships in Life cover at most half a cell a
+/// generation, so none can get from further in to the border between checks
+const ESCAPE_MARGIN: u32 = BORDER_MARGIN + CHECK_INTERVAL / 2;
+
+#[derive(Parser, Debug)]
+#[command(about = "Runs Conway's Game of Life on the GPU without a window, writing the result as RLE")]
+struct Cli {
+    /// Number of generations to run, or in a search the most each batch of soups is given to settle
+    #[arg(long, short)]
+    generations: u32,
+
+    /// World size in cells, `N` for a square world or `WxH`
+    #[arg(long, value_parser = parse_size, default_value = "4096")]
+    size: UVec2,
+
+    #[command(flatten)]
+    world: WorldArgs,
+
+    /// Run on a software adapter
+    #[arg(long)]
+    software: bool,
+
+    /// Where to write the final generation
+    #[arg(long, short, default_value = "out.rle")]
+    output: PathBuf,
+
+    /// Where to write statistics, next to the output with a `.json` extension by default
+    #[arg(long)]
+    stats: Option<PathBuf>,
+
+    /// Search this many 16x16 soups rather than running a single world, writing a census of the
+    /// objects left once they've settled and the spaceships which escaped them. Each batch of soups
+    /// uses the next seed, and runs until every soup repeats with a period of at most 15
+    #[arg(
+        long,
+        conflicts_with_all = ["pattern", "rule_file", "rule_shader", "birth_chance", "survival_chance", "update_fraction", "second_order"]
+    )]
+    search: Option<u64>,
+
+    /// Side of the tile each soup gets to itself in a search, a multiple of 32. Anything which
+    /// reaches the edge of its tile is lost
+    #[arg(long, default_value_t = 256)]
+    tile: u32,
+
+    /// Longest period objects are run for when being classified
+    #[arg(long, default_value_t = 128)]
+    max_period: u32,
+
+    /// Where to write the search census
+    #[arg(long, default_value = "census.json")]
+    census: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    adapter: String,
+    world_size: [u32; 2],
+    rule: String,
+    /// Pattern file the run started from
+    pattern: Option<PathBuf>,
+    /// Seed of the random soup, when there wasn't a pattern
+    seed: Option<u32>,
+    generations: u32,
+    /// Live cells in every generation, starting with the initial state
+    population: Vec<u64>,
+    /// Bounding box of the final generation, `None` if everything died
+    bounding_box: Option<BoundingBox>,
+    /// Hash of the final generation which doesn't depend on its position, as hex
+    hash: String,
+}
+
+#[derive(Serialize)]
+struct BoundingBox {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    if cli.search.is_some() && (cli.tile % u32::BITS != 0 || cli.size % cli.tile != UVec2::ZERO || cli.tile < 128) {
+        eprintln!("The tile size must be a multiple of 32, at least 128, which divides the world size");
+        return ExitCode::FAILURE;
+    }
+
+    let settings = match cli.world.settings(true) {
+        Ok(settings) => Settings {
+            world_size: cli.size,
+            ..settings
+        },
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let rule = settings.rule;
+
+    // Objects are classified by stepping them on the CPU, which only knows the 3x3 neighbourhood
+    if cli.search.is_some() && !rule.is_two_state_3x3() {
+        eprintln!("Searches only support two state rules on the 3x3 neighbourhood");
+        return ExitCode::FAILURE;
+    }
+
+    // Each generation of a one dimensional rule is the next row down, so the run stops at the last
+    if matches!(rule.kind, RuleKind::OneDimensional(_)) && settings.rule_table.is_none() && cli.generations >= cli.size.y {
+        eprintln!("A world {} cells tall only fits {} generations of a one dimensional rule", cli.size.y, cli.size.y - 1);
+        return ExitCode::FAILURE;
+    }
+
+    let context = match pollster::block_on(HeadlessContext::new(cli.software, settings.world_backend)) {
+        Ok(context) => context,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let HeadlessContext { device, queue, limits, adapter_config } = &context;
+    for note in &adapter_config.notes {
+        eprintln!("{note}");
+    }
+
+    if let Some(soups) = cli.search {
+        return search(&context, &cli, settings, soups);
+    }
+
+    let mut simulation = match Simulation::new(device, limits, adapter_config, &settings) {
+        Ok(simulation) => simulation,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    simulation.populate(device, queue, &settings);
+
+    let population = run(&context, &mut simulation, cli.generations);
+
+    let words = simulation.read_world(device, queue);
+    let (mut result, origin) = Pattern::from_packed(&words, simulation.world_size().x, simulation.world().cell_bits());
+    let rule_name = match (&settings.rule_table, &settings.rule_shader) {
+        (Some(table), _) => Some(table.name.clone()),
+        (None, Some(rule_shader)) => Some(rule_shader.name()),
+        (None, None) => None,
+    };
+    match &rule_name {
+        Some(name) => result.rule_name = Some(name.clone()),
+        None => result.rule = Some(rule),
+    }
+
+    if result.cells.len() as u64 != population.last().copied().unwrap_or_default() {
+        eprintln!("Warning: GPU population doesn't match the cells read back");
+    }
+
+    if let Err(err) = fs::write(&cli.output, result.to_rle()) {
+        eprintln!("Failed to write {}: {err}", cli.output.display());
+        return ExitCode::FAILURE;
+    }
+
+    let stats = Stats {
+        adapter: adapter_config.adapter.name.clone(),
+        world_size: simulation.world_size().to_array(),
+        rule: rule_name.unwrap_or_else(|| rule.to_string()),
+        pattern: cli.world.pattern,
+        seed: settings.pattern.is_none().then_some(cli.world.seed),
+        generations: cli.generations,
+        population,
+        bounding_box: origin.map(|origin| BoundingBox {
+            x: origin.x,
+            y: origin.y,
+            width: result.size.x,
+            height: result.size.y,
+        }),
+        hash: format!("{:016x}", result.hash()),
+    };
+
+    let stats_path = cli.stats.unwrap_or_else(|| cli.output.with_extension("json"));
+    if let Err(err) = fs::write(&stats_path, serde_json::to_string_pretty(&stats).unwrap()) {
+        eprintln!("Failed to write {}: {err}", stats_path.display());
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Runs batches of soups filling the world, one per tile, until they settle, adding the objects they
+/// settle into and the spaceships escaping them to a census
+fn search(context: &HeadlessContext, cli: &Cli, settings: Settings, soups: u64) -> ExitCode {
+    let HeadlessContext { device, queue, limits, adapter_config } = context;
+    let settings = Settings {
+        soup_tile: Some(cli.tile),
+        ..settings
+    };
+
+    let mut simulation = Simulation::new(device, limits, adapter_config, &settings)
+        .expect("Only rule shaders can fail to compile");
+    let width = simulation.world_size().x;
+    let tiles = settings.world_size / cli.tile;
+    let tile_count = tiles.element_product() as usize;
+    let batches = soups.div_ceil(tile_count as u64);
+
+    let mut census = Census::new(settings.rule, cli.max_period);
+    let mut remaining = soups;
+
+    for batch in 0..batches {
+        eprintln!("Batch {}/{batches}", batch + 1);
+
+        let seed = cli.world.seed.wrapping_add(batch as u32);
+        let batch_soups = remaining.min(tile_count as u64) as usize;
+        let soup_id = |tile: usize| SoupId { seed, tile: [tile as u32 % tiles.x, tile as u32 / tiles.x] };
+        simulation.set_seed(queue, seed);
+        simulation.init(device, queue);
+
+        // Period of every soup which has settled
+        let mut periods = vec![None; batch_soups];
+        let mut words = Vec::new();
+        for _ in 0..cli.generations.div_ceil(CHECK_INTERVAL) {
+            advance(context, &mut simulation, CHECK_INTERVAL - SETTLE_WINDOW);
+
+            let mut hashes = vec![Vec::new(); tile_count];
+            for _ in 0..SETTLE_WINDOW {
+                advance(context, &mut simulation, 1);
+                words = simulation.read_world(device, queue);
+                for (tile, hash) in tile_hashes(&words, width, tiles, cli.tile).into_iter().enumerate() {
+                    hashes[tile].push(hash);
+                }
+            }
+            for (period, hashes) in periods.iter_mut().zip(&hashes) {
+                *period = period.or_else(|| settled_period(hashes));
+            }
+            if periods.iter().all(Option::is_some) {
+                break;
+            }
+
+            let mut escaped = false;
+            for (tile, cells) in tile_cells(&words, width, tiles, cli.tile).iter().enumerate().take(batch_soups) {
+                if periods[tile].is_some() {
+                    continue;
+                }
+                for object in census::cluster(cells, census::SEPARATION) {
+                    if object.iter().any(|&cell| near_border(cell, cli.tile, ESCAPE_MARGIN)) && census.add_spaceship(&object, soup_id(tile)) {
+                        for cell in object {
+                            let cell = cell.as_uvec2();
+                            words[(cell.y * width / u32::BITS + cell.x / u32::BITS) as usize] &= !(1 << (u32::BITS - 1 - cell.x % u32::BITS));
+                        }
+                        escaped = true;
+                    }
+                }
+            }
+            if escaped {
+                simulation.write_world(queue, &words);
+            }
+        }
+
+        for (tile, cells) in tile_cells(&words, width, tiles, cli.tile).iter().enumerate().take(batch_soups) {
+            match periods[tile] {
+                Some(period) => {
+                    let objects = census::separate(cells, settings.rule, period)
+                        .into_iter()
+                        .filter(|object| !object.iter().any(|&cell| near_border(cell, cli.tile, BORDER_MARGIN)))
+                        .collect::<Vec<_>>();
+                    census.add_soup(&objects, soup_id(tile));
+                },
+                None => census.add_unsettled(soup_id(tile)),
+            }
+        }
+        remaining -= batch_soups as u64;
+    }
+
+    if let Err(err) = fs::write(&cli.census, serde_json::to_string_pretty(&census.report()).unwrap()) {
+        eprintln!("Failed to write {}: {err}", cli.census.display());
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Live cells of every tile of a world read back from the GPU, in world coordinates
+fn tile_cells(words: &[u32], width: u32, tiles: UVec2, tile: u32) -> Vec<Vec<IVec2>> {
+    let (pattern, origin) = Pattern::from_packed(words, width, 1);
+    let mut tile_cells = vec![Vec::new(); tiles.element_product() as usize];
+    for &cell in &pattern.cells {
+        let position = cell + origin.unwrap_or_default();
+        let index = position / tile;
+        // The world can be a little wider than asked for
+        if index.x < tiles.x {
+            tile_cells[(index.y * tiles.x + index.x) as usize].push(position.as_ivec2());
+        }
+    }
+    tile_cells
+}
+
+/// Hash of every tile's cells, which changes whenever anything in the tile does
+fn tile_hashes(words: &[u32], width: u32, tiles: UVec2, tile: u32) -> Vec<u64> {
+    let row_words = (width / u32::BITS) as usize;
+    let tile_words = (tile / u32::BITS) as usize;
+    let mut hashers = vec![DefaultHasher::new(); tiles.element_product() as usize];
+
+    for (y, row) in words.chunks(row_words).enumerate() {
+        let tile_y = y / tile as usize;
+        for (tile_x, words) in row.chunks(tile_words).take(tiles.x as usize).enumerate() {
+            hashers[tile_y * tiles.x as usize + tile_x].write(bytemuck::cast_slice(words));
+        }
+    }
+    hashers.iter().map(Hasher::finish).collect()
+}
+
+/// Shortest period up to [`MAX_SETTLED_PERIOD`] which the hashes of consecutive generations repeat
+/// with, if there is one
+fn settled_period(hashes: &[u64]) -> Option<u32> {
+    (1..=MAX_SETTLED_PERIOD).find(|&period| hashes.iter().zip(&hashes[period as usize..]).all(|(a, b)| a == b))
+}
+
+fn near_border(cell: IVec2, tile: u32, margin: u32) -> bool {
+    let local = cell.as_uvec2() % tile;
+    local.cmplt(UVec2::splat(margin)).any() || local.cmpge(UVec2::splat(tile - margin)).any()
+}
+
+/// Steps the simulation without recording anything
+fn advance(context: &HeadlessContext, simulation: &mut Simulation, generations: u32) {
+    let HeadlessContext { device, queue, .. } = context;
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    for generation in 1..=generations {
+        simulation.step(&mut encoder, None);
+
+        if generation % GENERATIONS_PER_SUBMIT == 0 {
+            let full = std::mem::replace(&mut encoder, device.create_command_encoder(&Default::default()));
+            queue.submit(std::iter::once(full.finish()));
+        }
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+/// Steps the simulation, returning the population of every generation including the first. Counts
+/// go into a buffer with room for one submission's worth, which is read back whenever it fills, so
+/// that any number of generations fits
+fn run(context: &HeadlessContext, simulation: &mut Simulation, generations: u32) -> Vec<u64> {
+    let HeadlessContext { device, queue, .. } = context;
+    let entry_size = size_of::<u64>() as u64;
+
+    let history = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("population_history"),
+        size: GENERATIONS_PER_SUBMIT as u64 * entry_size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut population = Vec::with_capacity(generations as usize + 1);
+    let mut encoder = device.create_command_encoder(&Default::default());
+    let mut counted = 0;
+
+    for generation in 0..=generations {
+        if generation > 0 {
+            simulation.step(&mut encoder, None);
+        }
+        simulation.count_population(&mut encoder, &history, counted * entry_size);
+        counted += 1;
+
+        if counted == GENERATIONS_PER_SUBMIT as u64 || generation == generations {
+            let full = std::mem::replace(&mut encoder, device.create_command_encoder(&Default::default()));
+            queue.submit(std::iter::once(full.finish()));
+
+            let counts = history.slice(..counted * entry_size);
+            counts.map_async(wgpu::MapMode::Read, |result| result.expect("Failed to map population history"));
+            device.poll(wgpu::Maintain::Wait);
+            population.extend_from_slice(bytemuck::cast_slice::<_, u64>(&counts.get_mapped_range()));
+            history.unmap();
+            counted = 0;
+        }
+    }
+
+    population
+}
@@ -0,0 +1,358 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::{ivec2, IVec2};
+use serde::Serialize;
+
+use crate::{apgcode, pattern::Pattern, rule::Rule};
+
+/// Cells closer than this (in either direction) are treated as part of the same object when they
+/// can't be told apart by running them
+pub const SEPARATION: i32 = 2;
+
+/// What an object turned out to be when run on its own
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObjectKind {
+    StillLife,
+    Oscillator { period: u32 },
+    Spaceship { period: u32, displacement: IVec2 },
+    /// Didn't repeat within the period limit on its own, or died, although the soup it came from had
+    /// settled
+    Unclassified,
+}
+
+impl ObjectKind {
+    fn name(self) -> &'static str {
+        match self {
+            ObjectKind::StillLife => "still_life",
+            ObjectKind::Oscillator { .. } => "oscillator",
+            ObjectKind::Spaceship { .. } => "spaceship",
+            ObjectKind::Unclassified => "unclassified",
+        }
+    }
+}
+
+/// Identifies a soup well enough to recreate it
+#[derive(Clone, Copy, Serialize, Debug)]
+pub struct SoupId {
+    pub seed: u32,
+    /// Position of the soup's tile in the world
+    pub tile: [u32; 2],
+}
+
+#[derive(Serialize, Debug)]
+pub struct CensusEntry {
+    /// Apgcode, or `zz_UNCLASSIFIED` for everything which couldn't be classified
+    pub object: String,
+    pub kind: &'static str,
+    pub period: u32,
+    /// Live cells in the canonical phase
+    pub population: usize,
+    pub count: u64,
+    /// First soup the object was seen in
+    pub first_soup: SoupId,
+    pub rle: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CensusReport {
+    pub rule: String,
+    pub soups: u64,
+    /// Objects by decreasing count
+    pub census: Vec<CensusEntry>,
+    /// Soups which were still changing when the search gave up on them, whose objects aren't in the
+    /// census apart from any spaceships which escaped them
+    pub unsettled: Vec<SoupId>,
+}
+
+/// Running tally of every object found in the soups searched so far
+pub struct Census {
+    rule: Rule,
+    max_period: u32,
+    soups: u64,
+    entries: HashMap<String, CensusEntry>,
+    unsettled: Vec<SoupId>,
+}
+
+impl Census {
+    pub fn new(rule: Rule, max_period: u32) -> Self {
+        Self {
+            rule,
+            max_period,
+            soups: 0,
+            entries: HashMap::new(),
+            unsettled: Vec::new(),
+        }
+    }
+
+    /// Counts each object a soup settled into, as given by [`separate`]
+    pub fn add_soup(&mut self, objects: &[Vec<IVec2>], soup: SoupId) {
+        self.soups += 1;
+
+        for object in objects {
+            let (kind, phases) = classify(object, self.rule, self.max_period);
+            self.count(kind, &phases, soup);
+        }
+    }
+
+    /// Notes a soup which never settled. Its objects are left out, as they're still changing
+    pub fn add_unsettled(&mut self, soup: SoupId) {
+        self.soups += 1;
+        self.unsettled.push(soup);
+    }
+
+    /// Counts an object on its way out of a soup if it's a spaceship, returning whether it was
+    pub fn add_spaceship(&mut self, cells: &[IVec2], soup: SoupId) -> bool {
+        let (kind, phases) = classify(cells, self.rule, self.max_period);
+        let spaceship = matches!(kind, ObjectKind::Spaceship { .. });
+        if spaceship {
+            self.count(kind, &phases, soup);
+        }
+        spaceship
+    }
+
+    fn count(&mut self, kind: ObjectKind, phases: &[Vec<IVec2>], soup: SoupId) {
+        let period = match kind {
+            ObjectKind::StillLife => 1,
+            ObjectKind::Oscillator { period } | ObjectKind::Spaceship { period, .. } => period,
+            ObjectKind::Unclassified => 0,
+        };
+        let (name, pattern) = match apgcode::canonical(kind, phases) {
+            Some(code) => {
+                let pattern = apgcode::decode(&code).expect("Canonical apgcodes should decode");
+                (code, pattern)
+            },
+            None => ("zz_UNCLASSIFIED".to_string(), Pattern::from_cells(&phases[0], None)),
+        };
+
+        self.entries
+            .entry(name.clone())
+            .or_insert_with(|| CensusEntry {
+                object: name,
+                kind: kind.name(),
+                period,
+                population: pattern.cells.len(),
+                count: 0,
+                first_soup: soup,
+                rle: Pattern { rule: Some(self.rule), ..pattern }.to_rle(),
+            })
+            .count += 1;
+    }
+
+    pub fn report(self) -> CensusReport {
+        let mut census = self.entries.into_values().collect::<Vec<_>>();
+        census.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.object.cmp(&b.object)));
+
+        CensusReport {
+            rule: self.rule.to_string(),
+            soups: self.soups,
+            census,
+            unsettled: self.unsettled,
+        }
+    }
+}
+
+/// Splits settled cells, which repeat every `period` generations, into objects the way apgsearch
+/// does. Cells touching in any phase, or touching the cells they were born from, belong to the same
+/// object. Objects which don't run the same on their own, such as two which give birth between them
+/// without touching, are then joined to anything within [`SEPARATION`] of them
+pub fn separate(cells: &[IVec2], rule: Rule, period: u32) -> Vec<Vec<IVec2>> {
+    let mut phases = vec![cells.iter().copied().collect::<HashSet<_>>()];
+    for _ in 1..period {
+        phases.push(step(phases.last().unwrap(), rule));
+    }
+
+    // Union-find over every cell alive in any phase
+    let mut parents = phases.iter().flatten().map(|&cell| (cell, cell)).collect::<HashMap<_, _>>();
+    fn root(parents: &mut HashMap<IVec2, IVec2>, mut cell: IVec2) -> IVec2 {
+        while parents[&cell] != cell {
+            let grandparent = parents[&parents[&cell]];
+            parents.insert(cell, grandparent);
+            cell = grandparent;
+        }
+        cell
+    }
+
+    // Each phase is joined up with itself and with the phase after it, which wraps around to the first
+    for (i, phase) in phases.iter().enumerate() {
+        let next = &phases[(i + 1) % phases.len()];
+        for &cell in phase {
+            for neighbour in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| cell + ivec2(dx, dy))) {
+                if phase.contains(&neighbour) || next.contains(&neighbour) {
+                    let root_cell = root(&mut parents, cell);
+                    let root_neighbour = root(&mut parents, neighbour);
+                    parents.insert(root_cell, root_neighbour);
+                }
+            }
+        }
+    }
+    let component = phases
+        .iter()
+        .flatten()
+        .map(|&cell| (cell, root(&mut parents, cell)))
+        .collect::<HashMap<_, _>>();
+
+    let mut components = HashMap::<IVec2, Vec<IVec2>>::new();
+    for &cell in cells {
+        components.entry(component[&cell]).or_default().push(cell);
+    }
+
+    // Run on its own, every phase of an object has to be its part of the same phase of the soup
+    let (independent, interacting): (Vec<_>, Vec<_>) = components.into_iter().partition(|(root, object)| {
+        let mut current = object.iter().copied().collect::<HashSet<_>>();
+        phases.iter().cycle().skip(1).take(phases.len()).all(|phase| {
+            current = step(&current, rule);
+            let expected = phase.iter().filter(|cell| component[cell] == *root).collect::<HashSet<_>>();
+            current.len() == expected.len() && current.iter().all(|cell| expected.contains(cell))
+        })
+    });
+
+    let interacting = interacting.into_iter().flat_map(|(_, object)| object).collect::<Vec<_>>();
+    independent.into_iter().map(|(_, object)| object).chain(cluster(&interacting, SEPARATION)).collect()
+}
+
+/// Groups cells into objects, joining any which are within `separation` of each other
+pub fn cluster(cells: &[IVec2], separation: i32) -> Vec<Vec<IVec2>> {
+    let index = cells.iter().enumerate().map(|(i, &cell)| (cell, i)).collect::<HashMap<_, _>>();
+    let mut seen = vec![false; cells.len()];
+    let mut objects = Vec::new();
+
+    for start in 0..cells.len() {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+
+        let mut object = Vec::new();
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            let cell = cells[i];
+            object.push(cell);
+
+            for dy in -separation..=separation {
+                for dx in -separation..=separation {
+                    if let Some(&j) = index.get(&(cell + ivec2(dx, dy))) {
+                        if !seen[j] {
+                            seen[j] = true;
+                            stack.push(j);
+                        }
+                    }
+                }
+            }
+        }
+        objects.push(object);
+    }
+
+    objects
+}
+
+/// Runs `cells` on an unbounded plane until it repeats, returning what it is along with every
+/// phase, each moved to the origin and sorted
+pub fn classify(cells: &[IVec2], rule: Rule, max_period: u32) -> (ObjectKind, Vec<Vec<IVec2>>) {
+    let (first, origin) = normalise(cells.iter().copied());
+    let mut phases = vec![first.clone()];
+    let mut current = cells.iter().copied().collect::<HashSet<_>>();
+
+    for period in 1..=max_period {
+        current = step(&current, rule);
+        if current.is_empty() {
+            break;
+        }
+
+        let (phase, phase_origin) = normalise(current.iter().copied());
+        if phase == first {
+            let displacement = phase_origin - origin;
+            let kind = match (period, displacement) {
+                (1, IVec2::ZERO) => ObjectKind::StillLife,
+                (_, IVec2::ZERO) => ObjectKind::Oscillator { period },
+                _ => ObjectKind::Spaceship { period, displacement },
+            };
+            return (kind, phases);
+        }
+        phases.push(phase);
+    }
+
+    (ObjectKind::Unclassified, vec![first])
+}
+
+/// One generation without any boundary
+pub fn step(cells: &HashSet<IVec2>, rule: Rule) -> HashSet<IVec2> {
+    let neighbourhood = |cell: IVec2| (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| cell + ivec2(dx, dy)))
+        .fold(0, |neighbourhood, cell| (neighbourhood << 1) | cells.contains(&cell) as u32);
+
+    cells
+        .iter()
+        .flat_map(|&cell| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| cell + ivec2(dx, dy))))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter(|&cell| rule.next(neighbourhood(cell)))
+        .collect()
+}
+
+/// Moves cells so that their bounding box starts at the origin and sorts them into reading order.
+/// Also returns where the bounding box was
+pub fn normalise(cells: impl Iterator<Item = IVec2>) -> (Vec<IVec2>, IVec2) {
+    let mut cells = cells.collect::<Vec<_>>();
+    let min = cells.iter().copied().reduce(IVec2::min).unwrap_or_default();
+
+    for cell in &mut cells {
+        *cell -= min;
+    }
+    cells.sort_unstable_by_key(|cell| (cell.y, cell.x));
+
+    (cells, min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(coordinates: &[(i32, i32)]) -> Vec<IVec2> {
+        coordinates.iter().map(|&(x, y)| ivec2(x, y)).collect()
+    }
+
+    fn sizes(objects: &[Vec<IVec2>]) -> Vec<usize> {
+        let mut sizes = objects.iter().map(Vec::len).collect::<Vec<_>>();
+        sizes.sort_unstable();
+        sizes
+    }
+
+    #[test]
+    fn separate_block_and_blinker() {
+        let soup = cells(&[(0, 0), (1, 0), (0, 1), (1, 1), (4, 0), (5, 0), (6, 0)]);
+        let objects = separate(&soup, Rule::LIFE, 2);
+        assert_eq!(sizes(&objects), [3, 4]);
+    }
+
+    #[test]
+    fn separate_interacting() {
+        // Two L shapes which would each become a block, but crowd out the births between them
+        let soup = cells(&[(0, 1), (0, 2), (1, 2), (2, 0), (3, 0), (3, 1)]);
+        assert_eq!(step(&soup.iter().copied().collect(), Rule::LIFE), soup.iter().copied().collect());
+        assert_eq!(cluster(&soup, 1).len(), 2);
+
+        let objects = separate(&soup, Rule::LIFE, 1);
+        assert_eq!(sizes(&objects), [6]);
+    }
+
+    #[test]
+    fn classify_glider() {
+        let glider = cells(&[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        let (kind, phases) = classify(&glider, Rule::LIFE, 16);
+        assert_eq!(kind, ObjectKind::Spaceship { period: 4, displacement: ivec2(1, 1) });
+        assert_eq!(phases.len(), 4);
+    }
+
+    #[test]
+    fn classify_still_life_and_oscillator() {
+        assert_eq!(classify(&cells(&[(0, 0), (1, 0), (0, 1), (1, 1)]), Rule::LIFE, 16).0, ObjectKind::StillLife);
+        assert_eq!(classify(&cells(&[(0, 0), (1, 0), (2, 0)]), Rule::LIFE, 16).0, ObjectKind::Oscillator { period: 2 });
+    }
+
+    #[test]
+    fn classify_dying() {
+        let (kind, phases) = classify(&cells(&[(0, 0), (1, 0)]), Rule::LIFE, 16);
+        assert_eq!(kind, ObjectKind::Unclassified);
+        assert_eq!(phases, [cells(&[(0, 0), (1, 0)])]);
+    }
+}
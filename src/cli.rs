@@ -0,0 +1,157 @@
+//! Arguments shared by the binaries
+
+use std::{fmt::Display, path::{Path, PathBuf}, str::FromStr};
+
+use clap::Args;
+use glam::{uvec2, IVec2, UVec2};
+
+use crate::{CellPacking, Pattern, Rule, RuleShader, RuleTable, Settings, WorldBackend};
+
+/// Parses `X,Y`
+pub fn parse_pair<T: FromStr>(s: &str) -> Result<[T; 2], String> {
+    let (x, y) = s.split_once(',').ok_or_else(|| format!("expected `X,Y`, got '{s}'"))?;
+    let parse = |v: &str| v.trim().parse::<T>().map_err(|_| format!("'{v}' is not a valid number"));
+    Ok([parse(x)?, parse(y)?])
+}
+
+/// Parses `N` as a square or `WxH`
+pub fn parse_size(s: &str) -> Result<UVec2, String> {
+    let parse = |v: &str| match v.trim().parse::<u32>() {
+        Ok(0) | Err(_) => Err(format!("'{v}' is not a valid size")),
+        Ok(n) => Ok(n),
+    };
+
+    match s.split_once('x') {
+        Some((width, height)) => Ok(uvec2(parse(width)?, parse(height)?)),
+        None => parse(s).map(UVec2::splat),
+    }
+}
+
+/// Parses a probability, such as a density or the chance of something happening
+pub fn parse_probability(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(probability) if (0. ..=1.).contains(&probability) => Ok(probability),
+        _ => Err(format!("'{s}' is not a probability between 0 and 1")),
+    }
+}
+
+/// Flags for the rule and the world it runs in, shared by the binaries
+#[derive(Args, Debug)]
+pub struct WorldArgs {
+    /// Rule in B/S notation, such as B36/S23 or B2-a/S12, a MAP string, a Generations rule such as
+    /// B2/S/C3, a one dimensional rule such as W30, or Immigration or QuadLife for Life in colour.
+    /// Defaults to the pattern's rule, then B3/S23
+    #[arg(long)]
+    pub rule: Option<Rule>,
+
+    /// Golly `.rule` file with a `@TABLE` to run instead of a B/S rule, for cells with more than two states
+    #[arg(long, conflicts_with = "rule")]
+    pub rule_file: Option<PathBuf>,
+
+    /// WGSL file with `fn next_state(neighbours: u32, alive: bool) -> bool`, deciding each cell from
+    /// its count of live Moore neighbours in place of the rule's births and survivals
+    #[arg(long, conflicts_with = "rule_file")]
+    pub rule_shader: Option<PathBuf>,
+
+    /// RLE or plaintext (.cells) file to start from instead of a random soup
+    #[arg(long)]
+    pub pattern: Option<PathBuf>,
+
+    /// Position of the pattern's top left corner as `X,Y`. Centred by default
+    #[arg(long, value_parser = parse_pair::<i32>, allow_hyphen_values = true)]
+    pub offset: Option<[i32; 2]>,
+
+    /// Seed for the random soup
+    #[arg(long, default_value_t = 0)]
+    pub seed: u32,
+
+    /// Chance of each cell in the random soup starting alive
+    #[arg(long, value_parser = parse_probability, default_value_t = 0.5)]
+    pub density: f32,
+
+    /// Chance of each birth actually happening, for noisy two state rules. Rolled for every cell and
+    /// generation from the seed
+    #[arg(long, value_parser = parse_probability, default_value_t = 1., conflicts_with_all = ["rule_file", "second_order"])]
+    pub birth_chance: f32,
+
+    /// Chance of each survival actually happening, otherwise the cell dies
+    #[arg(long, value_parser = parse_probability, default_value_t = 1., conflicts_with_all = ["rule_file", "second_order"])]
+    pub survival_chance: f32,
+
+    /// Fraction of cells updated each generation, picked at random from the seed, the rest staying as
+    /// they were
+    #[arg(long, value_parser = parse_probability, default_value_t = 1., conflicts_with_all = ["rule_file", "second_order"])]
+    pub update_fraction: f32,
+
+    /// World backend (`texture-array` or `storage-buffer`), the best one the adapter supports by default
+    #[arg(long)]
+    pub backend: Option<WorldBackend>,
+
+    /// Pack 128 cells into each texel rather than 32
+    #[arg(long)]
+    pub wide: bool,
+
+    /// XOR each generation with the one before, making the rule reversible. The viewer reverses time
+    /// with R
+    #[arg(long, conflicts_with = "rule_file")]
+    pub second_order: bool,
+}
+
+impl WorldArgs {
+    /// Loads the files the flags name and checks the rule can do everything they ask of it, giving
+    /// settings with what the flags cover filled in. `runs_rule` is false when something else, such
+    /// as Lenia, runs in place of the rule, which then isn't checked
+    pub fn settings(&self, runs_rule: bool) -> Result<Settings, String> {
+        fn load<T, E: Display>(path: Option<&Path>, load: impl FnOnce(&Path) -> Result<T, E>) -> Result<Option<T>, String> {
+            path.map(|path| load(path).map_err(|err| format!("Failed to load {}: {err}", path.display())))
+                .transpose()
+        }
+
+        let pattern = load(self.pattern.as_deref(), Pattern::load)?;
+        let rule_table = load(self.rule_file.as_deref(), RuleTable::load)?;
+        let rule_shader = load(self.rule_shader.as_deref(), RuleShader::load)?;
+
+        let rule = self.rule
+            .or(pattern.as_ref().and_then(|pattern| pattern.rule))
+            .unwrap_or_default();
+
+        if runs_rule {
+            // Rules which aren't in B/S notation name a rule table
+            if let Some(name) = pattern.as_ref().filter(|pattern| pattern.rule.is_none()).and_then(|pattern| pattern.rule_name.as_ref()) {
+                if self.rule.is_none() && rule_table.is_none() {
+                    return Err(format!("The pattern's rule '{name}' isn't in B/S notation, load its rule table with --rule-file"));
+                }
+            }
+
+            if rule_shader.is_some() && !rule.supports_rule_shader() {
+                return Err(format!("Rule shaders can only replace rules which count live Moore neighbours, not {rule}"));
+            }
+            if self.second_order && !rule.supports_second_order() {
+                return Err(format!("Only two state rules which update every cell can be second order, not {rule}"));
+            }
+            if (self.birth_chance < 1. || self.survival_chance < 1.) && !rule.supports_stochastic() {
+                return Err(format!("Only two state rules which update every cell on its own can leave births and survivals to chance, not {rule}"));
+            }
+            if self.update_fraction < 1. && !rule.supports_asynchronous() {
+                return Err(format!("Only rules which update every cell on its own can update a fraction of them, not {rule}"));
+            }
+        }
+
+        Ok(Settings {
+            packing: if self.wide { CellPacking::Vector } else { CellPacking::Scalar },
+            world_backend: self.backend,
+            rule,
+            rule_table,
+            rule_shader,
+            pattern,
+            pattern_offset: self.offset.map(IVec2::from),
+            seed: self.seed,
+            density: self.density,
+            birth_chance: self.birth_chance,
+            survival_chance: self.survival_chance,
+            update_fraction: self.update_fraction,
+            second_order: self.second_order,
+            ..Default::default()
+        })
+    }
+}
@@ -3,9 +3,10 @@ use std::time::Duration;
 use egui::{Align2, Context};
 use egui_wgpu::ScreenDescriptor;
 use egui_winit::{winit::{event::WindowEvent, window::Window}, State};
+use glam::UVec2;
 use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
 
-use crate::{RendererContext, WORLD_SIZE};
+use crate::{rendering_utils::Profiler, AdapterConfig, ProfileScope, RendererContext};
 
 // https://github.com/ejb004/egui-wgpu-demo/blob/master/src/gui.rs
 pub struct GuiRenderer {
@@ -59,17 +60,18 @@ impl GuiRenderer {
         mut run_ui: impl FnMut(&Context),
         encoder: &mut CommandEncoder,
         output_view: &TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
     ) {
         if !self.enabled {
             return;
         }
 
-        let raw_input = self.state.take_egui_input(&window);
+        let raw_input = self.state.take_egui_input(window);
         let full_output = self.context.run(raw_input, |ui| {
             run_ui(ui);
         });
         
-        self.state.handle_platform_output(&window, full_output.platform_output);
+        self.state.handle_platform_output(window, full_output.platform_output);
 
         let tris = self.context.tessellate(full_output.shapes, full_output.pixels_per_point);
         for (id, image_delta) in &full_output.textures_delta.set {
@@ -93,7 +95,7 @@ impl GuiRenderer {
                 },
             })],
             depth_stencil_attachment: None,
-            timestamp_writes: None,
+            timestamp_writes,
             label: Some("egui main render pass"),
             occlusion_query_set: None,
         });
@@ -106,12 +108,69 @@ impl GuiRenderer {
     }
 }
 
-#[derive(Default)]
+/// Timings of a single [`ProfileScope`]
+struct ScopeStats {
+    scope: ProfileScope,
+    average: Duration,
+    min: Duration,
+    max: Duration,
+    /// Milliseconds, oldest first
+    history: Vec<f32>,
+}
+
 pub struct UiState {
-    pub update_time_per_frame: Duration
+    /// `None` when the adapter can't profile
+    pub update_time_per_frame: Option<Duration>,
+    /// Set when the user asks for the trace to be written out
+    pub export_trace: bool,
+    /// Result of the last trace export
+    pub trace_status: Option<String>,
+    /// Set when the user asks for a second order rule to run the other way
+    pub reverse_time: bool,
+    /// Whether time is running backwards, `None` when the rule can't be reversed
+    pub time_reversed: Option<bool>,
+    /// Naga's error for a rule shader which didn't compile, shown until the window is closed
+    pub rule_shader_error: Option<String>,
+    scopes: Vec<ScopeStats>,
+    adapter_config: AdapterConfig,
+    /// Number of cells updated by each frame's compute work
+    cells: f64,
 }
 
 impl UiState {
+    pub fn new(adapter_config: AdapterConfig, world_size: UVec2, generations_per_frame: u32) -> Self {
+        Self {
+            update_time_per_frame: None,
+            export_trace: false,
+            trace_status: None,
+            reverse_time: false,
+            time_reversed: None,
+            rule_shader_error: None,
+            scopes: Vec::new(),
+            adapter_config,
+            cells: world_size.as_dvec2().element_product() * generations_per_frame as f64,
+        }
+    }
+
+    pub fn update_profile(&mut self, profiler: &Profiler) {
+        let idx = ProfileScope::ComputeUpdate.index() as usize;
+        self.update_time_per_frame = Some(profiler.average_time(idx));
+
+        self.scopes = ProfileScope::ALL
+            .into_iter()
+            .map(|scope| {
+                let idx = scope.index() as usize;
+                ScopeStats {
+                    scope,
+                    average: profiler.average_time(idx),
+                    min: profiler.min_time(idx),
+                    max: profiler.max_time(idx),
+                    history: profiler.samples(idx).map(|time| time.as_secs_f32() * 1000.).collect(),
+                }
+            })
+            .collect();
+    }
+
     pub fn draw(&mut self, ctx: &Context) {
         egui::Window::new("Settings")
         // .vscroll(true)
@@ -121,13 +180,106 @@ impl UiState {
         .default_width(800.0)
         .resizable(true)
         .anchor(Align2::LEFT_TOP, [0.0, 0.0])
-        .show(&ctx, |ui| {
-            let secs_per_frame = self.update_time_per_frame.as_secs_f32();
-            ui.label(format!("ms / frame: {}ms", ((secs_per_frame * 1000. * 100.).round() / 100.)));
-            ui.label(format!("Gc / s: {}", (WORLD_SIZE.element_product() as f64 / secs_per_frame as f64 / 1e9 * 100.).round() / 100.));
-            ui.label(format!("ps / cell: {}", (secs_per_frame as f64 / WORLD_SIZE.element_product() as f64 / 1e-12 * 100.).round() / 100.));
-            ui.label(format!("max FPS: {}", (1. / secs_per_frame * 100.).round() / 100.));
-            ui.end_row();
+        .show(ctx, |ui| {
+            if let Some(update_time_per_frame) = self.update_time_per_frame {
+                let secs_per_frame = update_time_per_frame.as_secs_f32();
+                ui.label(format!("ms / frame: {}ms", ((secs_per_frame * 1000. * 100.).round() / 100.)));
+                ui.label(format!("Gc / s: {}", (self.cells / secs_per_frame as f64 / 1e9 * 100.).round() / 100.));
+                ui.label(format!("ps / cell: {}", (secs_per_frame as f64 / self.cells / 1e-12 * 100.).round() / 100.));
+                ui.label(format!("max FPS: {}", (1. / secs_per_frame * 100.).round() / 100.));
+                ui.end_row();
+            }
+
+            if !self.scopes.is_empty() {
+                ui.collapsing("Profiler", |ui| {
+                    egui::Grid::new("profiler_scopes").striped(true).show(ui, |ui| {
+                        ui.label("Scope");
+                        ui.label("avg");
+                        ui.label("min");
+                        ui.label("max");
+                        ui.end_row();
+
+                        for stats in &self.scopes {
+                            ui.label(stats.scope.label());
+                            ui.label(format_ms(stats.average));
+                            ui.label(format_ms(stats.min));
+                            ui.label(format_ms(stats.max));
+                            sparkline(ui, &stats.history);
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+
+            ui.horizontal(|ui| {
+                self.export_trace |= ui.button("Export trace (F5)").clicked();
+                if let Some(status) = &self.trace_status {
+                    ui.label(status);
+                }
+            });
+
+            if let Some(error) = &self.rule_shader_error {
+                egui::CollapsingHeader::new("Rule shader failed to compile, running the rule instead").default_open(true).show(ui, |ui| {
+                    ui.colored_label(ui.visuals().error_fg_color, egui::RichText::new(error).monospace());
+                });
+            }
+
+            if let Some(time_reversed) = self.time_reversed {
+                ui.horizontal(|ui| {
+                    self.reverse_time |= ui.button("Reverse time (R)").clicked();
+                    ui.label(if time_reversed { "Running backwards" } else { "Running forwards" });
+                });
+            }
+
+            ui.collapsing("Adapter", |ui| {
+                let adapter_config = &self.adapter_config;
+                ui.label(format!("{} ({:?})", adapter_config.adapter.name, adapter_config.adapter.backend));
+                ui.label(format!("World: {:?}", adapter_config.world_backend));
+                ui.label(format!("Max tile size: {}", adapter_config.max_tile_size));
+                for note in &adapter_config.notes {
+                    ui.colored_label(ui.visuals().warn_fg_color, note);
+                }
+            });
         });
     }
 }
+
+
+/// Removes the ANSI colour codes naga puts in its errors for terminals, which egui would show as text
+pub fn strip_colours(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Codes run from the escape to the letter which ends them, such as `\x1b[1;31m`
+            chars.by_ref().find(char::is_ascii_alphabetic);
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn format_ms(time: Duration) -> String {
+    format!("{:.3}ms", time.as_secs_f64() * 1000.)
+}
+
+/// Draws `values` as a line scaled to fit a small box
+fn sparkline(ui: &mut egui::Ui, values: &[f32]) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(120., 18.), egui::Sense::hover());
+    if values.len() < 2 {
+        return;
+    }
+
+    let max = values.iter().copied().fold(f32::EPSILON, f32::max);
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| egui::pos2(
+            rect.left() + rect.width() * i as f32 / (values.len() - 1) as f32,
+            rect.bottom() - rect.height() * value / max
+        ))
+        .collect();
+
+    ui.painter().add(egui::Shape::line(points, ui.visuals().widgets.noninteractive.fg_stroke));
+}
\ No newline at end of file
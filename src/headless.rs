@@ -0,0 +1,36 @@
+use crate::{AdapterConfig, WorldBackend};
+
+/// A device without a surface, for running the simulation without a window
+pub struct HeadlessContext {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub limits: wgpu::Limits,
+    pub adapter_config: AdapterConfig,
+}
+
+impl HeadlessContext {
+    /// `software` asks for a fallback (CPU) adapter, such as llvmpipe or WARP. Fails if the adapter
+    /// refuses the device
+    pub async fn new(software: bool, world_backend: Option<WorldBackend>) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: software,
+            })
+            .await
+            .expect("No compatible adapter found");
+
+        let adapter_config = AdapterConfig::negotiate(&adapter, world_backend);
+        let (device, queue, limits) = adapter_config.request_device(&adapter).await?;
+
+        Ok(Self {
+            device,
+            queue,
+            limits,
+            adapter_config,
+        })
+    }
+}
@@ -0,0 +1,188 @@
+//! Hensel notation for isotropic non-totalistic rules, such as the `2-a` in `B2-a/S12`, where a
+//! letter after a neighbour count picks out one arrangement of that many neighbours up to rotation
+//! and reflection
+
+/// Arrangements of the eight neighbours, bit `n` being set for the arrangement `n`. Each
+/// arrangement has a bit for every neighbour, clockwise from north in the least significant bit
+pub type NeighbourSet = [u64; 4];
+
+/// Letters for up to four neighbours, each with an arrangement it stands for. Five or more
+/// neighbours use the same letter for the opposite arrangement
+const LETTERS: [&[(char, u8)]; 5] = [
+    &[],
+    &[('c', 0b10), ('e', 0b1)],
+    &[('c', 0b1010), ('e', 0b101), ('k', 0b1001), ('a', 0b11), ('i', 0b10001), ('n', 0b100010)],
+    &[
+        ('c', 0b101010), ('e', 0b10101), ('k', 0b100101), ('a', 0b111), ('i', 0b10000011),
+        ('n', 0b1011), ('y', 0b101001), ('q', 0b100011), ('j', 0b1000011), ('r', 0b10011),
+    ],
+    &[
+        ('c', 0b10101010), ('e', 0b1010101), ('k', 0b1001011), ('a', 0b1111), ('i', 0b11011),
+        ('n', 0b10001011), ('y', 0b101011), ('q', 0b100111), ('j', 0b1010011), ('r', 0b10111),
+        ('t', 0b10010011), ('w', 0b1100011), ('z', 0b110011),
+    ],
+];
+
+fn letters(count: u32) -> impl Iterator<Item = (char, u8)> {
+    let opposite = count > 4;
+    LETTERS[count.min(8 - count) as usize]
+        .iter()
+        .map(move |&(letter, neighbours)| (letter, if opposite { !neighbours } else { neighbours }))
+}
+
+/// Every rotation and reflection of an arrangement
+fn symmetries(neighbours: u8) -> impl Iterator<Item = u8> {
+    (0..8).map(move |symmetry| {
+        // Corners and edges alternate, so turning a quarter moves each neighbour two places
+        let rotated = neighbours.rotate_left(2 * (symmetry % 4));
+        if symmetry < 4 {
+            rotated
+        } else {
+            // Flip left to right, which keeps north where it is
+            (0..8).filter(|i| rotated & (1 << i) != 0).fold(0, |flipped, i| flipped | (1 << ((8 - i) % 8)))
+        }
+    })
+}
+
+pub fn contains(set: &NeighbourSet, neighbours: u8) -> bool {
+    set[neighbours as usize / 64] & (1 << (neighbours % 64)) != 0
+}
+
+fn insert(set: &mut NeighbourSet, neighbours: u8) {
+    set[neighbours as usize / 64] |= 1 << (neighbours % 64);
+}
+
+/// Every arrangement matching a condition such as `12-a3ce`
+pub fn parse(conditions: &str) -> Result<NeighbourSet, String> {
+    let mut set = NeighbourSet::default();
+    let mut chars = conditions.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let count = match c.to_digit(10) {
+            Some(n @ 0..=8) => n,
+            _ => return Err(format!("'{c}' is not a neighbour count between 0 and 8")),
+        };
+
+        let negated = chars.next_if_eq(&'-').is_some();
+        let mut chosen = Vec::new();
+        while let Some(letter) = chars.next_if(char::is_ascii_alphabetic) {
+            let (_, neighbours) = letters(count)
+                .find(|&(l, _)| l == letter)
+                .ok_or_else(|| format!("'{letter}' isn't an arrangement of {count} neighbours"))?;
+            chosen.push(neighbours);
+        }
+
+        if negated && chosen.is_empty() {
+            return Err(format!("'-' after {count} should be followed by letters"));
+        }
+
+        for neighbours in (0..=u8::MAX).filter(|n| n.count_ones() == count) {
+            let matches = chosen.iter().any(|&letter| symmetries(letter).any(|n| n == neighbours));
+            if chosen.is_empty() || matches != negated {
+                insert(&mut set, neighbours);
+            }
+        }
+    }
+
+    Ok(set)
+}
+
+/// Whether rotating or reflecting an arrangement never changes whether it's in the set
+pub fn is_isotropic(set: &NeighbourSet) -> bool {
+    (0..=u8::MAX).all(|neighbours| symmetries(neighbours).all(|n| contains(set, n) == contains(set, neighbours)))
+}
+
+/// Shortest condition for an isotropic set, preferring letters which are in it
+pub fn write(set: &NeighbourSet) -> String {
+    let mut conditions = String::new();
+
+    for count in 0..=8 {
+        let (present, absent): (Vec<_>, Vec<_>) = letters(count)
+            .map(|(letter, neighbours)| (letter, contains(set, neighbours)))
+            .partition(|&(_, present)| present);
+
+        let all = (0..=u8::MAX).filter(|n| n.count_ones() == count).all(|n| contains(set, n));
+        if all {
+            conditions.push(char::from_digit(count, 10).unwrap());
+        } else if !present.is_empty() {
+            conditions.push(char::from_digit(count, 10).unwrap());
+            if absent.len() < present.len() {
+                conditions.push('-');
+                conditions.extend(absent.iter().map(|&(letter, _)| letter));
+            } else {
+                conditions.extend(present.iter().map(|&(letter, _)| letter));
+            }
+        }
+    }
+
+    conditions
+}
+
+/// Whether each neighbour count is either entirely in the set or entirely out of it, as a mask with
+/// bit `n` set for `n` neighbours. `None` if the set depends on more than the count
+pub fn totalistic(set: &NeighbourSet) -> Option<u16> {
+    (0..=8).try_fold(0, |mask, count| {
+        let mut matching = (0..=u8::MAX).filter(|n| n.count_ones() == count).map(|n| contains(set, n));
+        let first = matching.next().unwrap();
+        matching.all(|m| m == first).then_some(mask | ((first as u16) << count))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+
+    /// Index into a rule's table of a dead cell with these neighbours, rows of the 3x3 neighbourhood
+    /// from the top with the north west neighbour most significant
+    fn dead_neighbourhood(rows: [u32; 3]) -> u32 {
+        (rows[0] << 6) | (rows[1] << 3) | rows[2]
+    }
+
+    fn is_set(rule: &Rule, neighbourhood: u32) -> bool {
+        rule.table()[neighbourhood as usize / 32] & (1 << (neighbourhood % 32)) != 0
+    }
+
+    #[test]
+    fn round_trip() {
+        for rule in ["B2-a/S12", "B3/S23", "B2ci3/S", "B3-jr/S23-k4e"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+    }
+
+    #[test]
+    fn arrangements() {
+        let set = parse("2ci3").unwrap();
+        // Both pairs of corners a cell apart in every rotation, both pairs of opposite edges and all
+        // 56 arrangements of three
+        assert_eq!(set.iter().map(|bits| bits.count_ones()).sum::<u32>(), 4 + 2 + 56);
+        assert!(contains(&set, 0b1010));
+        assert!(contains(&set, 0b10001));
+        assert!(!contains(&set, 0b11));
+    }
+
+    #[test]
+    fn table() {
+        let rule = "B2ci3/S".parse::<Rule>().unwrap();
+        // 2c, corners a cell apart
+        assert!(is_set(&rule, dead_neighbourhood([0b001, 0b000, 0b001])));
+        assert!(is_set(&rule, dead_neighbourhood([0b101, 0b000, 0b000])));
+        // 2i, opposite edges
+        assert!(is_set(&rule, dead_neighbourhood([0b010, 0b000, 0b010])));
+        assert!(is_set(&rule, dead_neighbourhood([0b000, 0b101, 0b000])));
+        // 2a and 2e aren't included
+        assert!(!is_set(&rule, dead_neighbourhood([0b110, 0b000, 0b000])));
+        assert!(!is_set(&rule, dead_neighbourhood([0b010, 0b001, 0b000])));
+        // Every arrangement of three, and no survivals
+        assert!(is_set(&rule, dead_neighbourhood([0b100, 0b001, 0b010])));
+        assert_eq!((0..512).filter(|&n| is_set(&rule, n)).count(), 4 + 2 + 56);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("2z").is_err());
+        assert!("B2z/S23".parse::<Rule>().is_err());
+        assert!(parse("9").is_err());
+        assert!(parse("2-").is_err());
+    }
+}
@@ -12,6 +12,7 @@ pub struct HybridInputState<T> {
 }
 
 #[derive(Clone)]
+#[allow(dead_code)]
 pub enum InputSource {
     Key {
         state: bool,
@@ -102,8 +103,12 @@ impl<T> HybridInputState<T> where T: Copy + Eq {
 
         for (sources, ident) in &mut self.listeners {
             for source in sources.iter_mut() {
+                let was_pressed = source.pressed_amount(&self.gilrs) > 0.;
                 if source.handle_winit(event) {
-                    self.queue.push_front(*ident);
+                    // Listeners fire once per press, not on release or key repeat
+                    if !was_pressed && source.pressed_amount(&self.gilrs) > 0. {
+                        self.queue.push_front(*ident);
+                    }
                     consumed = true;
                 }
             }
@@ -118,8 +123,8 @@ impl<T> HybridInputState<T> where T: Copy + Eq {
 
     pub fn update_gamepad(&mut self) {
         while let Some(event) = self.gilrs.next_event() {
-            for (sources, ident) in &mut self.bindings {
-                for source in sources.iter_mut() {
+            for (sources, ident) in &self.listeners {
+                for source in sources {
                     if source.matches_gamepad(&event) {
                         self.queue.push_front(*ident);
                     }
@@ -0,0 +1,151 @@
+//! Lenia, a continuous cellular automaton where every cell holds a state between 0 and 1 which grows
+//! or shrinks with a weighted average of the states around it
+
+use std::{collections::HashMap, f32::consts::PI, fmt, str::FromStr};
+
+use glam::{ivec2, uvec2, IVec2, UVec2};
+use naga_oil::compose::ShaderDefValue;
+
+/// Parameters of a Lenia world with a single ring shaped kernel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lenia {
+    /// Radius of the kernel in cells
+    pub radius: f32,
+    /// Average state around a cell which makes it grow fastest
+    pub mu: f32,
+    /// How far the average can stray from `mu` before the cell starts to shrink instead
+    pub sigma: f32,
+    /// Fraction of the growth applied each step
+    pub dt: f32,
+}
+
+impl Default for Lenia {
+    /// Orbium, the best known Lenia glider
+    fn default() -> Self {
+        Self { radius: 13., mu: 0.15, sigma: 0.015, dt: 0.1 }
+    }
+}
+
+/// A cell within Lenia's kernel and its share of the weighted average, laid out as the shader reads it
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Default, Debug)]
+pub struct KernelTap {
+    /// Position relative to the cell being updated
+    pub offset: IVec2,
+    pub weight: f32,
+    _padding: u32,
+}
+
+impl Lenia {
+    /// Every cell reads the whole disc around it, so large kernels get slow quickly
+    pub const MAX_RADIUS: f32 = 32.;
+    /// Float cells take 32 times the memory of Life's, so Lenia worlds are far smaller by default
+    pub const DEFAULT_WORLD_SIZE: UVec2 = uvec2(1024, 1024);
+
+    fn validate(&self) -> Result<(), String> {
+        if !(1. ..=Self::MAX_RADIUS).contains(&self.radius) {
+            return Err(format!("Lenia's radius must be between 1 and {}, not {}", Self::MAX_RADIUS, self.radius));
+        }
+        if self.sigma.is_nan() || self.sigma <= 0. {
+            return Err(format!("Lenia's sigma must be positive, not {}", self.sigma));
+        }
+        if self.dt.is_nan() || self.dt <= 0. || self.dt > 1. {
+            return Err(format!("Lenia's dt must be between 0 and 1, not {}", self.dt));
+        }
+        Ok(())
+    }
+
+    pub fn shader_defs(&self) -> HashMap<String, ShaderDefValue> {
+        HashMap::from([
+            ("LENIA".into(), ShaderDefValue::Bool(true)),
+        ])
+    }
+
+    /// Every cell the kernel gives any weight to, with the weights adding up to 1. The kernel is a
+    /// smooth bump peaking halfway out to the radius, and zero beyond it
+    pub fn kernel(&self) -> Vec<KernelTap> {
+        let range = self.radius.ceil() as i32;
+        let mut taps = (-range..=range)
+            .flat_map(|dy| (-range..=range).map(move |dx| ivec2(dx, dy)))
+            .filter_map(|offset| {
+                let r = offset.as_vec2().length() / self.radius;
+                (r > 0. && r < 1.).then(|| KernelTap { offset, weight: (4. - 1. / (r * (1. - r))).exp(), _padding: 0 })
+            })
+            .collect::<Vec<_>>();
+
+        let total = taps.iter().map(|tap| tap.weight).sum::<f32>();
+        for tap in &mut taps {
+            tap.weight /= total;
+        }
+        taps
+    }
+
+    /// sRGB colour for 256 evenly spaced states, from dark blue through purple and orange to
+    /// pale yellow
+    pub fn colours() -> Vec<[u8; 3]> {
+        const STOPS: [[f32; 3]; 5] = [
+            [0., 0., 4.],
+            [80., 18., 123.],
+            [182., 54., 121.],
+            [251., 136., 97.],
+            [252., 253., 191.],
+        ];
+
+        (0..256)
+            .map(|i| {
+                let position = i as f32 / 255. * (STOPS.len() - 1) as f32;
+                let stop = (position as usize).min(STOPS.len() - 2);
+                // Ease between stops so the bands don't show
+                let t = (1. - ((position - stop as f32) * PI).cos()) / 2.;
+                std::array::from_fn(|c| (STOPS[stop][c] + (STOPS[stop + 1][c] - STOPS[stop][c]) * t).round() as u8)
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Lenia {
+    type Err = String;
+
+    /// Accepts `RADIUS,MU,SIGMA,DT`, such as `13,0.15,0.015,0.1` for Orbium
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f32>().map_err(|_| format!("'{value}' is not a number")))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let [radius, mu, sigma, dt] = values[..] else {
+            return Err(format!("Lenia takes RADIUS,MU,SIGMA,DT, not '{s}'"));
+        };
+
+        let lenia = Self { radius, mu, sigma, dt };
+        lenia.validate()?;
+        Ok(lenia)
+    }
+}
+
+impl fmt::Display for Lenia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.radius, self.mu, self.sigma, self.dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel() {
+        let lenia = Lenia::default();
+        let kernel = lenia.kernel();
+
+        assert!((kernel.iter().map(|tap| tap.weight).sum::<f32>() - 1.).abs() < 1e-5);
+        assert!(kernel.iter().all(|tap| tap.weight > 0. && tap.offset.as_vec2().length() < lenia.radius));
+        assert!(!kernel.iter().any(|tap| tap.offset == IVec2::ZERO));
+
+        // Heaviest halfway out, and the same all the way round
+        let weight = |offset: IVec2| kernel.iter().find(|tap| tap.offset == offset).unwrap().weight;
+        assert!(weight(ivec2(6, 0)) > weight(ivec2(2, 0)));
+        assert!(weight(ivec2(6, 0)) > weight(ivec2(12, 0)));
+        assert_eq!(weight(ivec2(3, 4)), weight(ivec2(-4, 3)));
+    }
+}
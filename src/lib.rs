@@ -1,15 +1,43 @@
 pub mod gui;
+mod adapter_config;
 mod rendering_utils;
 mod input;
-
-use std::{borrow::Cow, collections::HashMap, num::NonZero, sync::Arc, time::{Duration, Instant}};
-
-use glam::{uvec2, vec2, UVec2, Vec2};
+mod trace;
+mod world;
+mod simulation;
+mod headless;
+mod rule;
+mod hensel;
+mod lenia;
+mod turmite;
+mod rule_shader;
+mod rule_table;
+mod pattern;
+pub mod apgcode;
+pub mod bench;
+pub mod census;
+pub mod cli;
+
+pub use adapter_config::AdapterConfig;
+pub use headless::HeadlessContext;
+pub use lenia::Lenia;
+pub use pattern::{Pattern, PatternError};
+pub use rule::{LargerThanLife, Margolus, Neighbourhood, OneDimensional, Rule, RuleKind};
+pub use rule_shader::RuleShader;
+pub use rule_table::{RuleTable, RuleTableError};
+pub use simulation::Simulation;
+pub use turmite::{Ant, Transition, Turmite, Turn};
+pub use world::{CellPacking, WorldBackend};
+
+use std::{borrow::Cow, path::PathBuf, sync::Arc, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use glam::{uvec2, vec2, IVec2, UVec2, Vec2};
 use gui::{GuiRenderer, UiState};
 use input::{HybridInputState, InputSource};
-use naga_oil::compose::{ComposableModuleDescriptor, Composer, NagaModuleDescriptor, ShaderDefValue};
+use naga_oil::compose::{NagaModuleDescriptor, ShaderDefValue};
 use rendering_utils::{Profiler, SimpleUniformHelper};
-use wgpu::{naga::valid::Capabilities, CommandEncoder, ShaderStages, TextureView};
+use trace::Trace;
+use wgpu::{CommandEncoder, ShaderStages, TextureView};
 use winit::{
     application::ApplicationHandler, dpi::PhysicalSize, event::{ElementState, KeyEvent, TouchPhase, WindowEvent}, event_loop::EventLoop, keyboard::{KeyCode, PhysicalKey}, window::Window
 };
@@ -17,6 +45,8 @@ use winit::{
 struct AppState {
     renderer: RendererContext<'static>,
     game_of_life: GameOfLifeState,
+    profiler: Option<Profiler>,
+    trace: Trace,
     gui: GuiRenderer,
     ui_state: UiState,
     window: Arc<Window>, // FIXME: I really dislike the use of an `Arc` here but I can't find a way around it
@@ -24,18 +54,110 @@ struct AppState {
 
 pub struct App {
     last_update: Instant,
+    settings: Settings,
     state: Option<AppState>,
 }
 
-impl Default for App {
-    fn default() -> Self {
+impl App {
+    pub fn new(settings: Settings) -> Self {
         Self {
             last_update: Instant::now(),
+            settings,
             state: None,
         }
     }
 }
 
+impl Default for App {
+    fn default() -> Self {
+        Self::new(Settings::default())
+    }
+}
+
+/// Options which are fixed for the lifetime of the simulation
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub packing: CellPacking,
+    /// Forces a particular world backend rather than picking the best one the adapter supports
+    pub world_backend: Option<WorldBackend>,
+    /// Size of the world in cells
+    pub world_size: UVec2,
+    pub rule: Rule,
+    /// Golly rule table to run instead of `rule`, giving cells more than two states
+    pub rule_table: Option<RuleTable>,
+    /// WGSL deciding births and survivals in place of `rule`'s, for rules where
+    /// [`Rule::supports_rule_shader`]. The GUI shows why it failed to compile and runs `rule` instead
+    pub rule_shader: Option<RuleShader>,
+    /// Runs Lenia instead of either rule, with a float state in every cell
+    pub lenia: Option<Lenia>,
+    /// Runs ants following this turmite instead of a rule, on a world which starts empty. Ants write
+    /// cells with atomics, so the world is always kept in storage buffers
+    pub turmite: Option<Turmite>,
+    /// Number of ants when running a turmite, see [`Turmite::place_ants`]
+    pub ants: u32,
+    /// Steps each ant takes every generation
+    pub ant_steps: u32,
+    /// XORs each generation of `rule` with the one before, which makes it reversible. Only works
+    /// for rules where [`Rule::supports_second_order`], and texture array worlds always use
+    /// [`CellPacking::Scalar`]
+    pub second_order: bool,
+    /// Starting pattern, the world starts as a random soup without one
+    pub pattern: Option<Pattern>,
+    /// Position of the pattern's top left corner, centred by default
+    pub pattern_offset: Option<IVec2>,
+    /// Seed for the initial soup
+    pub seed: u32,
+    /// Chance of each cell in the initial soup being alive
+    pub density: f32,
+    /// Chances of each birth and each survival of a two state rule actually happening, otherwise the
+    /// cell stays dead or dies. Rolled for every cell and generation from the seed
+    pub birth_chance: f32,
+    pub survival_chance: f32,
+    /// Fraction of cells updated each generation, picked at random from the seed, the rest staying as
+    /// they were. Rules updating a row or block at a time always update every cell
+    pub update_fraction: f32,
+    /// Splits the world into tiles this many cells across, each with its own small soup in the
+    /// middle and a dead border so that soups can't interact
+    pub soup_tile: Option<u32>,
+    pub present_mode: wgpu::PresentMode,
+    /// Where the camera starts looking, the centre of the world by default
+    pub camera_centre: Option<Vec2>,
+    pub zoom: f32,
+    pub start_paused: bool,
+    pub generations_per_frame: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            packing: CellPacking::default(),
+            world_backend: None,
+            world_size: DEFAULT_WORLD_SIZE,
+            rule: Rule::default(),
+            rule_table: None,
+            rule_shader: None,
+            lenia: None,
+            turmite: None,
+            ants: 1,
+            ant_steps: Turmite::DEFAULT_STEPS_PER_GENERATION,
+            second_order: false,
+            pattern: None,
+            pattern_offset: None,
+            seed: 0,
+            density: 0.5,
+            birth_chance: 1.,
+            survival_chance: 1.,
+            update_fraction: 1.,
+            soup_tile: None,
+            present_mode: wgpu::PresentMode::Fifo,
+            camera_centre: None,
+            zoom: 1.,
+            start_paused: false,
+            generations_per_frame: 1,
+        }
+    }
+}
+
 pub struct RendererContext<'a> {
     surface: wgpu::Surface<'a>,
     device: wgpu::Device,
@@ -43,18 +165,52 @@ pub struct RendererContext<'a> {
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,   
     limits: wgpu::Limits,
+    adapter_config: AdapterConfig,
 }
 
 struct GameOfLifeState {
     render_pipeline: wgpu::RenderPipeline,
+    /// Draws the ants of a turmite over the world
+    marker_pipeline: Option<wgpu::RenderPipeline>,
     fragment_bind_groups: [wgpu::BindGroup; 2],
-    compute_pipeline: wgpu::ComputePipeline,
-    init_pipeline: wgpu::ComputePipeline,
-    compute_bind_groups: [wgpu::BindGroup; 2],
-    frame_polarity: bool,
+    simulation: Simulation,
+    paused: bool,
+    generations_per_frame: u32,
+    /// Generations run so far, which for a one dimensional rule is the row the camera follows
+    generation: u32,
+    one_dimensional: bool,
+    /// Whether a second order rule is being run back towards its first generation
+    backwards: bool,
+    /// Why the rule shader failed to compile, if it did
+    rule_shader_error: Option<String>,
     camera: SimpleUniformHelper<CameraUniform>,
+    palette: SimpleUniformHelper<PaletteUniform>,
     input: HybridInputState<InputIdent>,
-    profiler: Profiler
+}
+
+/// GPU passes timed by the profiler
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProfileScope {
+    ComputeUpdate,
+    WorldRender,
+    Egui,
+}
+
+impl ProfileScope {
+    pub const ALL: [ProfileScope; 3] = [ProfileScope::ComputeUpdate, ProfileScope::WorldRender, ProfileScope::Egui];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProfileScope::ComputeUpdate => "Compute update",
+            ProfileScope::WorldRender => "World render",
+            ProfileScope::Egui => "egui",
+        }
+    }
+
+    #[inline(always)]
+    pub fn index(self) -> u32 {
+        self as u32
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -62,46 +218,56 @@ enum InputIdent {
     Up,
     Down,
     Left,
-    Right
+    Right,
+    TogglePause,
+    ReverseTime,
 }
 
-pub fn run() {
+pub fn run(settings: Settings) {
     let event_loop = EventLoop::new().unwrap();
 
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-    let mut app: App = App::default();
+    let mut app = App::new(settings);
 
     event_loop.run_app(&mut app).unwrap();
 }
 
 
 impl AppState {
-    async fn new(window: Window) -> Self {
+    async fn new(window: Window, settings: &Settings) -> Result<Self, String> {
         let window = Arc::new(window);
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
     
-        let renderer = RendererContext::new(window.clone(), &instance).await;
-        let game_of_life = GameOfLifeState::new(&renderer);
+        let renderer = RendererContext::new(window.clone(), &instance, settings).await?;
+        let mut game_of_life = GameOfLifeState::new(&renderer, settings);
         let gui = GuiRenderer::new(&renderer.device, renderer.config.format, None, 1, &window);
+        let profiler = renderer.adapter_config.timestamps
+            .then(|| Profiler::new(&ProfileScope::ALL.map(ProfileScope::label), 100, &renderer.device, renderer.queue.get_timestamp_period()));
 
-        Self {
+        let mut ui_state = UiState::new(renderer.adapter_config.clone(), settings.world_size, settings.generations_per_frame);
+        ui_state.rule_shader_error = game_of_life.rule_shader_error.take();
+
+        Ok(Self {
             game_of_life,
+            profiler,
+            trace: Trace::new(TRACE_CAPACITY),
             gui,
+            ui_state,
             renderer,
             window,
-            ui_state: UiState::default(),
-        }
+        })
     }
 }
 
 impl App {
-    async fn set_state(&mut self, window: Window) {
+    async fn set_state(&mut self, window: Window) -> Result<(), String> {
         if self.state.is_some() {
-            return;
+            return Ok(());
         }
 
-        let state = AppState::new(window).await;
+        let state = AppState::new(window, &self.settings).await?;
         self.state = Some(state);
+        Ok(())
     }
 }
 
@@ -111,7 +277,10 @@ impl ApplicationHandler for App {
             .create_window(Window::default_attributes())
             .unwrap();
 
-        pollster::block_on(self.set_state(window));
+        if let Err(err) = pollster::block_on(self.set_state(window)) {
+            eprintln!("{err}");
+            event_loop.exit();
+        }
     }
 
     fn window_event(
@@ -125,7 +294,11 @@ impl ApplicationHandler for App {
             None => return
         };
 
-        if state.gui.handle_input(&state.window, &event) || state.game_of_life.handle_input(&event){
+        let consumed = state.trace.cpu_scope("input", || {
+            state.gui.handle_input(&state.window, &event) || state.game_of_life.handle_input(&event)
+        });
+
+        if consumed {
             // Event has been consumed
             return;
         }
@@ -142,28 +315,45 @@ impl ApplicationHandler for App {
                 ..
             } => event_loop.exit(),
             WindowEvent::KeyboardInput { 
-                event,
-                ..
-            } => match event {
-                KeyEvent {
+                event: KeyEvent {
                     state: ElementState::Pressed,
                     physical_key: PhysicalKey::Code(KeyCode::F3),
                     ..
-                } => state.gui.enabled = !state.gui.enabled,
-                _ => {}
-            }
+                },
+                ..
+            } => state.gui.enabled = !state.gui.enabled,
+            WindowEvent::KeyboardInput { 
+                event: KeyEvent {
+                    state: ElementState::Pressed,
+                    physical_key: PhysicalKey::Code(KeyCode::F5),
+                    ..
+                },
+                ..
+            } => state.export_trace(),
             WindowEvent::Resized(size) => {
                 state.renderer.resize(size);
                 state.game_of_life.resize(size);
             }
             WindowEvent::RedrawRequested => {
                 state.window.request_redraw();
-                state.game_of_life.step_camera(self.last_update.elapsed());
+                state.game_of_life.process_events();
+                state.ui_state.time_reversed = state.game_of_life.time_reversed();
+                state.trace.cpu_scope("camera step", || state.game_of_life.step_camera(self.last_update.elapsed()));
 
                 self.last_update = Instant::now();
                 match state.render() {
                     Ok(_) => {
-                        state.ui_state.update_time_per_frame = state.game_of_life.profiler.average_time(0);
+                        if let Some(profiler) = &mut state.profiler {
+                            state.ui_state.update_profile(profiler);
+                            state.trace.gpu_spans(profiler.take_timeline());
+                        }
+
+                        if std::mem::take(&mut state.ui_state.export_trace) {
+                            state.export_trace();
+                        }
+                        if std::mem::take(&mut state.ui_state.reverse_time) {
+                            state.game_of_life.reverse_time();
+                        }
                     }
 
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
@@ -185,12 +375,15 @@ impl ApplicationHandler for App {
     }
 }
 
-const BITS_PER_PIXEL: u32 = 32;
+const BITS_PER_WORD: u32 = 32;
 
-const WORLD_WIDTH: u32 = 1 << 16;
-const WORLD_SIZE: UVec2 = uvec2(WORLD_WIDTH, WORLD_WIDTH);
-const TEXTURE_SIZE: UVec2 = uvec2(WORLD_WIDTH / BITS_PER_PIXEL, WORLD_WIDTH);
-const WORKGROUP_DIMS: UVec2 = TEXTURE_SIZE.saturating_div(uvec2(8, 8));
+/// Number of events kept for exporting as a trace
+const TRACE_CAPACITY: usize = 50_000;
+
+/// World size for rules, see [`Lenia::DEFAULT_WORLD_SIZE`] and [`Turmite::DEFAULT_WORLD_SIZE`] for
+/// the others
+pub const DEFAULT_WORLD_SIZE: UVec2 = uvec2(1 << 16, 1 << 16);
+const WORKGROUP_SIZE: UVec2 = uvec2(8, 8);
 
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Default, Debug)]
@@ -201,9 +394,40 @@ struct CameraUniform {
     _padding: u32,
 }
 
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+struct PaletteUniform {
+    /// Linear colour of every state
+    colours: [[f32; 4]; rule_table::MAX_STATES as usize],
+}
+
+impl PaletteUniform {
+    /// A colour map for Lenia, otherwise colours from the turmite, the rule table if there is one or
+    /// the rule
+    fn new(settings: &Settings) -> Self {
+        let srgb = match (&settings.lenia, &settings.turmite, &settings.rule_table) {
+            (Some(_), _, _) => Lenia::colours(),
+            (None, Some(turmite), _) => turmite.colours(),
+            (None, None, Some(table)) => table.colours.clone(),
+            (None, None, None) => settings.rule.colours(),
+        };
+
+        let to_linear = |channel: u8| {
+            let c = channel as f32 / 255.;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        };
+
+        let mut colours = [[0.; 4]; rule_table::MAX_STATES as usize];
+        for (colour, [r, g, b]) in colours.iter_mut().zip(srgb) {
+            *colour = [to_linear(r), to_linear(g), to_linear(b), 1.];
+        }
+        Self { colours }
+    }
+}
+
 impl GameOfLifeState {
     pub fn handle_input(&mut self, event: &WindowEvent) -> bool {
-        return match event {
+        match event {
             WindowEvent::MouseWheel { delta, phase: TouchPhase::Moved, .. } => {
                 match delta {
                     winit::event::MouseScrollDelta::LineDelta(_, amount) => self.camera.update_inner(|camera| {
@@ -222,6 +446,29 @@ impl GameOfLifeState {
         }
     }
 
+    pub fn process_events(&mut self) {
+        while let Some(event) = self.input.next_event() {
+            match event {
+                InputIdent::TogglePause => self.paused = !self.paused,
+                InputIdent::ReverseTime => self.reverse_time(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Runs a second order rule the other way, which retraces its generations exactly
+    pub fn reverse_time(&mut self) {
+        if self.simulation.is_second_order() {
+            self.simulation.reverse_time();
+            self.backwards = !self.backwards;
+        }
+    }
+
+    /// `None` unless the rule is second order and so can be reversed
+    pub fn time_reversed(&self) -> Option<bool> {
+        self.simulation.is_second_order().then_some(self.backwards)
+    }
+
     pub fn step_camera(&mut self, delta_time: Duration) {
         let delta_time = delta_time.as_secs_f32();
         
@@ -233,44 +480,66 @@ impl GameOfLifeState {
         });
     }
 
-    pub fn new(renderer: &RendererContext) -> Self {
-        let mut composer = Composer::default()
-            .with_capabilities(Capabilities::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING);
-
-        composer.add_composable_module(ComposableModuleDescriptor {
-            source: include_str!("wgsl/conway_common.wgsl"),
-            file_path: "wgsl/conway_common.wgsl",
-            ..Default::default()
-        }).unwrap();
+    pub fn new(renderer: &RendererContext, settings: &Settings) -> Self {
+        let mut composer = simulation::composer();
+        // A rule shader which doesn't compile is reported in the GUI, running the built-in rule instead
+        let (simulation, rule_shader_error) = match Simulation::new(&renderer.device, &renderer.limits, &renderer.adapter_config, settings) {
+            Ok(simulation) => (simulation, None),
+            Err(error) => {
+                let settings = Settings { rule_shader: None, ..settings.clone() };
+                let simulation = Simulation::new(&renderer.device, &renderer.limits, &renderer.adapter_config, &settings)
+                    .expect("Only rule shaders can fail to compile");
+                (simulation, Some(gui::strip_colours(&error)))
+            },
+        };
+        let world = simulation.world();
 
-        let world = World::new(renderer.limits.max_texture_dimension_2d, &renderer.device);
-            
         let fragment_bind_group_layout = world.fragment_bind_group_layout(&renderer.device);
         let fragment_bind_groups = world.fragment_bind_groups(&fragment_bind_group_layout, &renderer.device);
 
+        let hexagonal = settings.lenia.is_none() && settings.rule_table.is_none() && settings.rule.neighbourhood() == Neighbourhood::Hexagonal;
+
+        let one_dimensional = settings.lenia.is_none() && settings.rule_table.is_none() && matches!(settings.rule.kind, RuleKind::OneDimensional(_));
+
+        let mut centre = settings.camera_centre.unwrap_or_else(|| if one_dimensional {
+            // Start with the first generation along the top of the screen
+            vec2(settings.world_size.x as f32 / 2., renderer.config.height as f32 * settings.zoom / 2.)
+        } else {
+            (settings.world_size / 2).as_vec2()
+        });
+        if hexagonal {
+            // Rows are drawn moved along, see the renderer
+            centre.x -= centre.y.floor() / 2.;
+        }
+
         let camera = SimpleUniformHelper::from_inner(CameraUniform {
-            centre: (WORLD_SIZE / 2).as_vec2(),
-            // centre: vec2(32., 0.),
-            zoom: 1.,
+            centre,
+            zoom: settings.zoom,
             screen_resolution: vec2(renderer.config.width as f32, renderer.config.height as f32),
             ..Default::default()
-        }, &renderer.device, ShaderStages::FRAGMENT);
-            
+        }, &renderer.device, ShaderStages::VERTEX_FRAGMENT);
+
+        let palette = SimpleUniformHelper::from_inner(PaletteUniform::new(settings), &renderer.device, ShaderStages::FRAGMENT);
 
         let render_pipeline_layout =
             renderer.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("render Pipeline Layout"),
-                bind_group_layouts: &[&fragment_bind_group_layout, camera.layout()],
+                bind_group_layouts: &[&fragment_bind_group_layout, camera.layout(), palette.layout()],
                 push_constant_ranges: &[],
             });
         
 
+        let mut render_defs = world.shader_defs();
+        if hexagonal {
+            render_defs.insert("HEXAGONAL".into(), ShaderDefValue::Bool(true));
+        }
+
         let graphics_shader = renderer.device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Renderer"),
             source: wgpu::ShaderSource::Naga(Cow::Owned(composer.make_naga_module(NagaModuleDescriptor {
                 source: include_str!("wgsl/conway_renderer.wgsl"),
                 file_path: "wgsl/conway_renderer.wgsl",
-                shader_defs: world.shader_defs(),
+                shader_defs: render_defs,
                 ..Default::default()
             }).unwrap()))
         });
@@ -312,47 +581,59 @@ impl GameOfLifeState {
             multiview: None,
             cache: None,
         });
-        
-        println!("{:?}", world.shader_defs());
-
-        let compute_shader = renderer.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute"),
-            source: wgpu::ShaderSource::Naga(Cow::Owned(composer.make_naga_module(NagaModuleDescriptor {
-                source: include_str!("wgsl/conway_compute.wgsl"),
-                file_path: "wgsl/conway_compute.wgsl",
-                shader_defs: world.shader_defs(),
-                ..Default::default()
-            }).unwrap()))
-        });
 
-        let compute_bind_group_layout = world.compute_bind_group_layout(&renderer.device);
-
-        let compute_bind_groups = world.compute_bind_groups(&compute_bind_group_layout, &renderer.device);
-        
-        let compute_pipeline_layout = renderer.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[&compute_bind_group_layout],
-            label: Some("compute_pipeline_layout"),
-            push_constant_ranges: &[]
-        });
+        let marker_pipeline = simulation.ants().map(|_| {
+            let shader = renderer.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Turmite markers"),
+                source: wgpu::ShaderSource::Naga(Cow::Owned(composer.make_naga_module(NagaModuleDescriptor {
+                    source: include_str!("wgsl/turmite_markers.wgsl"),
+                    file_path: "wgsl/turmite_markers.wgsl",
+                    ..Default::default()
+                }).unwrap()))
+            });
 
-        let compute_pipeline = renderer.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            cache: None,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            entry_point: Some("update"),
-            label: Some("update_pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader
-        });
+            let layout = renderer.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("marker_pipeline_layout"),
+                bind_group_layouts: &[camera.layout()],
+                push_constant_ranges: &[],
+            });
 
-        let init_pipeline = renderer.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            cache: None,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            entry_point: Some("init"),
-            label: Some("init_pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader
+            renderer.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Marker Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    // Each ant is an instance, read straight from the simulation's buffer
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: size_of::<Ant>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![0 => Uint32x2, 1 => Uint32],
+                    }],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: renderer.config.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
         });
-
+        
         const DEAD_ZONE: f32 = 0.2;
 
         let input = HybridInputState::new(
@@ -362,73 +643,74 @@ impl GameOfLifeState {
                 (&[InputSource::key(KeyCode::KeyA), InputSource::key(KeyCode::ArrowLeft), InputSource::axis(gilrs::Axis::LeftStickX, |a|  (10f32).powf((-a - DEAD_ZONE).max(0.)) - 1.)], InputIdent::Left),
                 (&[InputSource::key(KeyCode::KeyD), InputSource::key(KeyCode::ArrowRight), InputSource::axis(gilrs::Axis::LeftStickX, |a| (10f32).powf(( a - DEAD_ZONE).max(0.)) - 1.)], InputIdent::Right)
             ],
-            &[]
+            &[
+                (&[InputSource::key(KeyCode::Space), InputSource::GamepadButton(gilrs::Button::Start)], InputIdent::TogglePause),
+                (&[InputSource::key(KeyCode::KeyR), InputSource::GamepadButton(gilrs::Button::Select)], InputIdent::ReverseTime)
+            ]
         );
     
-        let profiler = Profiler::new(1, 100, &renderer.device, renderer.queue.get_timestamp_period());
+        simulation.populate(&renderer.device, &renderer.queue, settings);
 
-        let state = Self {
-            compute_bind_groups,
-            compute_pipeline,
-            init_pipeline,
+        Self {
             fragment_bind_groups,
-            frame_polarity: false,
+            simulation,
+            paused: settings.start_paused,
+            generations_per_frame: settings.generations_per_frame,
+            generation: 0,
+            one_dimensional,
+            backwards: false,
+            rule_shader_error,
             render_pipeline,
+            marker_pipeline,
             camera,
+            palette,
             input,
-            profiler
-        };
-
-        state.init(renderer);
-
-        state
+        }
     }
 
-    pub fn init(&self, renderer: &RendererContext) {
-        let mut encoder = renderer.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Init Encoder")
-        });
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Init Pass"),
-                timestamp_writes: None
+    pub fn render(&mut self, renderer: &RendererContext, view: &TextureView, render_encoder: &mut CommandEncoder, profiler: Option<&Profiler>) {
+        if self.one_dimensional {
+            // Scroll down with the space-time diagram once the latest row reaches the bottom of the
+            // screen, which stops at the bottom of the world along with the run
+            let row = self.generation as f32 + 1.;
+            self.camera.update_inner(|camera| {
+                camera.centre.y = camera.centre.y.max(row - camera.screen_resolution.y * camera.zoom / 2.);
             });
-
-            compute_pass.set_pipeline(&self.init_pipeline);
-            compute_pass.set_bind_group(0, &self.compute_bind_groups[1], &[]);
-            
-            compute_pass.dispatch_workgroups(WORKGROUP_DIMS.x, WORKGROUP_DIMS.y, 1);
         }
-        renderer.queue.submit(std::iter::once(encoder.finish()));
-        println!("Initialise");
-    }
-
-    pub fn render(&mut self, renderer: &RendererContext, view: &TextureView, render_encoder: &mut CommandEncoder) {
         self.camera.update_buffer(&renderer.queue);
 
         let mut encoder = renderer.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Compute Encoder")
         });
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Compute Pass"),
-                timestamp_writes: Some(self.profiler.compute_pass_timestamp_writes(0))
-            });
-
-            compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0, &self.compute_bind_groups[self.frame_polarity as usize], &[]);
-            
-            compute_pass.dispatch_workgroups(WORKGROUP_DIMS.x, WORKGROUP_DIMS.y, 1);
+        if !self.paused {
+            let timestamp_writes = profiler.map(|profiler| profiler.compute_pass_timestamp_writes(ProfileScope::ComputeUpdate.index()));
+
+            // A one dimensional rule has nowhere to write once its latest row is the world's last
+            let last_row = self.simulation.world_size().y - 1;
+            let generations = if self.one_dimensional {
+                self.generations_per_frame.min(last_row - self.generation)
+            } else {
+                self.generations_per_frame
+            };
+
+            // Time every generation in the frame as a single span
+            for generation in 0..generations {
+                self.simulation.step(&mut encoder, timestamp_writes.as_ref().map(|writes| wgpu::ComputePassTimestampWrites {
+                    query_set: writes.query_set,
+                    beginning_of_pass_write_index: writes.beginning_of_pass_write_index.filter(|_| generation == 0),
+                    end_of_pass_write_index: writes.end_of_pass_write_index.filter(|_| generation + 1 == generations),
+                }));
+            }
+            self.generation += generations;
+            self.paused = self.one_dimensional && self.generation == last_row;
         }
-        self.profiler.resolve(&mut encoder);
         renderer.queue.submit(std::iter::once(encoder.finish()));
-        self.profiler.process_results(&renderer.device);
         
         {
             let mut render_pass = render_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Load,
@@ -437,16 +719,22 @@ impl GameOfLifeState {
                 })],
                 depth_stencil_attachment: None,
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes: profiler.map(|profiler| profiler.render_pass_timestamp_writes(ProfileScope::WorldRender.index())),
             });
 
             render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.fragment_bind_groups[0], &[]);
+            render_pass.set_bind_group(0, &self.fragment_bind_groups[self.simulation.current()], &[]);
             render_pass.set_bind_group(1, self.camera.bind_group(), &[]);
+            render_pass.set_bind_group(2, self.palette.bind_group(), &[]);
             render_pass.draw(0..3, 0..1);
+
+            if let (Some(marker_pipeline), Some((ants, count))) = (&self.marker_pipeline, self.simulation.ants()) {
+                render_pass.set_pipeline(marker_pipeline);
+                render_pass.set_bind_group(0, self.camera.bind_group(), &[]);
+                render_pass.set_vertex_buffer(0, ants.slice(..));
+                render_pass.draw(0..3, 0..count);
+            }
         }
-        
-        self.frame_polarity = !self.frame_polarity;
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
@@ -457,175 +745,8 @@ impl GameOfLifeState {
     }
 }
 
-struct World {
-    textures: [Vec<wgpu::Texture>; 2],
-}
-
-impl World {
-    fn new(max_texture_size: u32, device: &wgpu::Device) -> Self {
-        let grid_dims = uvec2(TEXTURE_SIZE.x.div_ceil(max_texture_size), TEXTURE_SIZE.y.div_ceil(max_texture_size));
-        let tiles = grid_dims.element_product();
-
-        let gen_textures = || {
-            let mut textures = Vec::with_capacity(tiles as usize);
-
-            for y in 0..grid_dims.y {
-                for x in 0..grid_dims.x {
-                    let desc = wgpu::TextureDescriptor {
-                        label: Some("world_tile"),
-                        size: wgpu::Extent3d {
-                            width: (TEXTURE_SIZE.x - x * max_texture_size).min(max_texture_size),
-                            height: (TEXTURE_SIZE.y - y * max_texture_size).min(max_texture_size),
-                            depth_or_array_layers: 1,
-                        },
-                        mip_level_count: 1,
-                        sample_count: 1,
-                        dimension: wgpu::TextureDimension::D2,
-                        format: wgpu::TextureFormat::R32Uint,
-                        usage: wgpu::TextureUsages::STORAGE_BINDING,
-                        view_formats: &[],
-                    };
-
-                    textures.push(device.create_texture(&desc));
-                }
-            }
-            textures
-        };
-
-        Self {
-            textures: [gen_textures(), gen_textures()]
-        }
-    }
-
-    fn fragment_bind_group_layout(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
-        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::ReadOnly,
-                        format: wgpu::TextureFormat::R32Uint,
-                        view_dimension: wgpu::TextureViewDimension::D2
-                    },
-                    count: NonZero::new(self.textures[0].len() as u32),
-                }
-            ]
-        })
-    }
-
-    fn fragment_bind_groups(&self, layout: &wgpu::BindGroupLayout, device: &wgpu::Device) -> [wgpu::BindGroup; 2] {
-        let views = self.textures
-            .iter()
-            .map(|grid| grid.iter()
-                .map(|texture| texture.create_view(&Default::default()))
-                .collect::<Vec<_>>())
-            .collect::<Vec<_>>();
-
-        let view_refs = views
-            .iter()
-            .map(|grid| grid
-                .iter()
-                .collect::<Vec<_>>())
-            .collect::<Vec<_>>();
-
-        view_refs.iter()    
-            .map(|grid| device.create_bind_group({
-                &wgpu::BindGroupDescriptor {
-                    label: None,
-                    layout,
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureViewArray(&grid)
-                    }],
-                }
-            }))
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap()
-    }
-
-    fn compute_bind_group_layout(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
-        let texture_count= NonZero::new(self.textures[0].len() as u32);
-
-        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    count: texture_count,
-                    ty: wgpu::BindingType::StorageTexture { 
-                        access: wgpu::StorageTextureAccess::ReadOnly,
-                        format: wgpu::TextureFormat::R32Uint,
-                        view_dimension: wgpu::TextureViewDimension::D2
-                    },
-                    visibility: wgpu::ShaderStages::COMPUTE
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    count: texture_count,
-                    ty: wgpu::BindingType::StorageTexture { 
-                        access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: wgpu::TextureFormat::R32Uint,
-                        view_dimension: wgpu::TextureViewDimension::D2
-                    },
-                    visibility: wgpu::ShaderStages::COMPUTE
-                },
-            ]
-        })
-    }
-
-    fn compute_bind_groups(&self, layout: &wgpu::BindGroupLayout, device: &wgpu::Device) -> [wgpu::BindGroup; 2] {
-        let views = self.textures
-            .iter()
-            .map(|grid| grid.iter()
-                .map(|texture| texture.create_view(&Default::default()))
-                .collect::<Vec<_>>())
-            .collect::<Vec<_>>();
-
-        let view_refs = views
-            .iter()
-            .map(|grid| grid
-                .iter()
-                .collect::<Vec<_>>())
-            .collect::<Vec<_>>();
-
-
-        [[&view_refs[0], &view_refs[1]], [&view_refs[1], &view_refs[0]]]
-            .into_iter()
-            .map(|[grid_a, grid_b]| device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureViewArray(grid_a)
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureViewArray(grid_b)
-                    },
-                ],
-            }))
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap()
-    }
-
-    fn shader_defs(&self) -> HashMap<String, naga_oil::compose::ShaderDefValue> {
-        let tile_width = self.textures[0][0].width();
-        let max_tile_dim = self.textures[0][0].height().max(tile_width);
-        HashMap::from([
-            ("TILE_SIZE".into(), ShaderDefValue::UInt(max_tile_dim)),
-            ("GRID_WIDTH".into(), ShaderDefValue::UInt(TEXTURE_SIZE.x.div_ceil(tile_width))),
-            ("GRID_LENGTH".into(), ShaderDefValue::UInt(self.textures[0].len() as u32)),
-        ])
-    }
-}
-
 impl RendererContext<'static> {
-    async fn new(window: Arc<Window>, instance: &wgpu::Instance) -> RendererContext<'static> {
+    async fn new(window: Arc<Window>, instance: &wgpu::Instance, settings: &Settings) -> Result<RendererContext<'static>, String> {
         let size = window.inner_size();
         let surface: wgpu::Surface = instance.create_surface(window).unwrap();
         
@@ -637,30 +758,16 @@ impl RendererContext<'static> {
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
-        
-        let limits = adapter.limits();
+            .expect("No compatible adapter found");
+
+        let world_backend = match settings.turmite {
+            Some(_) => Some(WorldBackend::StorageBuffer),
+            None => settings.world_backend,
+        };
+        let adapter_config = AdapterConfig::negotiate(&adapter, world_backend);
 
         // Connection to the device
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES |
-                        wgpu::Features::TIMESTAMP_QUERY |
-                        wgpu::Features::TEXTURE_BINDING_ARRAY |
-                        wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY |
-                        wgpu::Features::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
-                    required_limits: wgpu::Limits {
-                        max_texture_dimension_2d: WORLD_SIZE.y.min(limits.max_texture_dimension_2d),
-                        ..Default::default()
-                    },
-                    label: None,
-                    memory_hints: wgpu::MemoryHints::default(),
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let (device, queue, limits) = adapter_config.request_device(&adapter).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -671,7 +778,16 @@ impl RendererContext<'static> {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
         
-        println!("{:?}", adapter.get_info());
+        let present_mode = if surface_caps.present_modes.contains(&settings.present_mode)
+            || matches!(settings.present_mode, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync)
+        {
+            settings.present_mode
+        } else {
+            println!("Present mode {:?} unsupported, using Fifo", settings.present_mode);
+            wgpu::PresentMode::Fifo
+        };
+
+        println!("{:?}", adapter_config);
         println!("{:?}", surface_format);
 
         let config = wgpu::SurfaceConfiguration {
@@ -679,20 +795,21 @@ impl RendererContext<'static> {
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
 
-        Self {
+        Ok(Self {
             config,
             device,
             queue,
             size,
             surface,
-            limits
-        }
+            limits,
+            adapter_config
+        })
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -707,8 +824,11 @@ impl RendererContext<'static> {
 
 impl AppState {
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let acquire_start = Instant::now();
         let output = self.renderer.surface.get_current_texture()?;
+        self.trace.cpu_span("acquire", acquire_start);
 
+        let encode_start = Instant::now();
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -717,13 +837,45 @@ impl AppState {
             label: Some("render encoder")
         });
 
-        self.game_of_life.render(&self.renderer, &view, &mut render_encoder);
-        self.gui.draw(&self.renderer, &self.window, |ctx| self.ui_state.draw(ctx), &mut render_encoder, &view);
+        self.game_of_life.render(&self.renderer, &view, &mut render_encoder, self.profiler.as_ref());
+        self.gui.draw(
+            &self.renderer,
+            &self.window,
+            |ctx| self.ui_state.draw(ctx),
+            &mut render_encoder,
+            &view,
+            self.profiler
+                .as_ref()
+                .filter(|_| self.gui.enabled)
+                .map(|profiler| profiler.render_pass_timestamp_writes(ProfileScope::Egui.index()))
+        );
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.resolve(&mut render_encoder);
+        }
+        let command_buffer = render_encoder.finish();
+        self.trace.cpu_span("encode", encode_start);
 
-        self.renderer.queue.submit(core::iter::once(render_encoder.finish()));
+        self.trace.cpu_scope("submit", || self.renderer.queue.submit(core::iter::once(command_buffer)));
 
-        output.present();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.process_results(&self.renderer.device);
+        }
+
+        self.trace.cpu_scope("present", || output.present());
 
         Ok(())
     }
+
+    fn export_trace(&mut self) {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = PathBuf::from(format!("trace-{secs}.json"));
+
+        let status = match self.trace.write_chrome_trace(&path) {
+            Ok(()) => format!("Wrote {}", path.display()),
+            Err(err) => format!("Failed to write {}: {err}", path.display()),
+        };
+        println!("{status}");
+        self.ui_state.trace_status = Some(status);
+    }
 }
\ No newline at end of file
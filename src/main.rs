@@ -1,5 +1,149 @@
-use conway_wgpu::run;
+use std::process::ExitCode;
 
-fn main() {
-    run();
+use clap::{Parser, ValueEnum};
+use conway_wgpu::{bench::{self, BenchOptions}, cli::{parse_pair, parse_size, WorldArgs}, run, DEFAULT_WORLD_SIZE, Lenia, Settings, Turmite, WorldBackend};
+use glam::{UVec2, Vec2};
+
+#[derive(Parser, Debug)]
+#[command(about = "Conway's Game of Life on the GPU")]
+struct Cli {
+    /// World size in cells, `N` for a square world or `WxH`. 65536 by default, 1024 for Lenia or
+    /// 4096 for turmites
+    #[arg(long, value_parser = parse_size)]
+    size: Option<UVec2>,
+
+    #[command(flatten)]
+    world: WorldArgs,
+
+    /// Run Lenia, where cells are continuous, instead of a rule. Takes `RADIUS,MU,SIGMA,DT`, which
+    /// is Orbium's 13,0.15,0.015,0.1 by default
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "13,0.15,0.015,0.1",
+        conflicts_with_all = ["rule", "rule_file", "rule_shader", "birth_chance", "survival_chance", "wide", "second_order"]
+    )]
+    lenia: Option<Lenia>,
+
+    /// Run ants over an empty world instead of a rule. Takes a string of turns for each colour such
+    /// as Langton's ant `RL` or `LLRR`, or a Golly turmite table such as `{{{1,2,0},{0,8,0}}}`. Ants
+    /// always use the storage buffer backend
+    #[arg(
+        long,
+        conflicts_with_all = ["rule", "rule_file", "rule_shader", "lenia", "birth_chance", "survival_chance", "update_fraction", "second_order"]
+    )]
+    turmite: Option<Turmite>,
+
+    /// Number of ants, the first in the middle of the world and the rest scattered around it. Ants
+    /// move side by side, so where two reach the same cell at once which goes first can differ
+    /// between runs
+    #[arg(long, requires = "turmite", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=Turmite::MAX_ANTS as i64))]
+    ants: u32,
+
+    /// Steps each ant takes every generation. Software adapters stop after 65535
+    #[arg(long, requires = "turmite", default_value_t = Turmite::DEFAULT_STEPS_PER_GENERATION, value_parser = clap::value_parser!(u32).range(1..))]
+    ant_steps: u32,
+
+    #[arg(long, value_enum, default_value_t = PresentMode::Fifo)]
+    present_mode: PresentMode,
+
+    /// Cell the camera starts centred on as `X,Y`. The centre of the world by default
+    #[arg(long, value_parser = parse_pair::<f32>, allow_hyphen_values = true)]
+    camera: Option<[f32; 2]>,
+
+    /// Starting zoom, in cells per screen pixel
+    #[arg(long, default_value_t = 1.)]
+    zoom: f32,
+
+    /// Start with the simulation paused, toggle with Space
+    #[arg(long)]
+    paused: bool,
+
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    generations_per_frame: u32,
+
+    /// Print a JSON performance report instead of opening a window
+    #[arg(long)]
+    bench: bool,
+
+    /// Benchmark on a software adapter
+    #[arg(long, requires = "bench")]
+    software: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PresentMode {
+    Fifo,
+    FifoRelaxed,
+    Mailbox,
+    Immediate,
+    AutoVsync,
+    AutoNoVsync,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::FifoRelaxed => wgpu::PresentMode::FifoRelaxed,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::AutoVsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::AutoNoVsync => wgpu::PresentMode::AutoNoVsync,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    if cli.bench {
+        let report = bench::run(BenchOptions {
+            software: cli.software,
+            world_backend: cli.world.backend,
+        });
+        return match report {
+            Ok(report) => {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                ExitCode::SUCCESS
+            },
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            },
+        };
+    }
+
+    if cli.turmite.is_some() && cli.world.backend == Some(WorldBackend::TextureArray) {
+        eprintln!("Ants write cells with atomics, which texture array worlds don't have");
+        return ExitCode::FAILURE;
+    }
+
+    let settings = match cli.world.settings(cli.lenia.is_none() && cli.turmite.is_none()) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    run(Settings {
+        world_size: cli.size.unwrap_or(match (&cli.lenia, &cli.turmite) {
+            (Some(_), _) => Lenia::DEFAULT_WORLD_SIZE,
+            (None, Some(_)) => Turmite::DEFAULT_WORLD_SIZE,
+            (None, None) => DEFAULT_WORLD_SIZE,
+        }),
+        lenia: cli.lenia,
+        turmite: cli.turmite,
+        ants: cli.ants,
+        ant_steps: cli.ant_steps,
+        present_mode: cli.present_mode.into(),
+        camera_centre: cli.camera.map(Vec2::from),
+        zoom: cli.zoom,
+        start_paused: cli.paused,
+        generations_per_frame: cli.generations_per_frame,
+        ..settings
+    });
+
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,398 @@
+use std::{fmt, fs, io, path::Path};
+
+use glam::{uvec2, IVec2, UVec2};
+
+use crate::rule::Rule;
+
+/// A pattern loaded from an RLE or plaintext (`.cells`) file
+#[derive(Clone, Default, Debug)]
+pub struct Pattern {
+    /// Bounding size given by the file, every cell is inside it
+    pub size: UVec2,
+    /// Live cells, relative to the top left corner
+    pub cells: Vec<UVec2>,
+    /// State of each of `cells` for patterns with more than two states, empty otherwise
+    pub states: Vec<u8>,
+    /// Rule from the RLE header, if there was one in B/S notation
+    pub rule: Option<Rule>,
+    /// Rule from the RLE header as written, which may name a rule table
+    pub rule_name: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Io(err) => write!(f, "{err}"),
+            PatternError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl From<io::Error> for PatternError {
+    fn from(err: io::Error) -> Self {
+        PatternError::Io(err)
+    }
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> PatternError {
+    PatternError::Parse { line: line + 1, message: message.into() }
+}
+
+impl Pattern {
+    pub fn load(path: &Path) -> Result<Self, PatternError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Works out the format from the contents, as extensions aren't reliable
+    pub fn parse(source: &str) -> Result<Self, PatternError> {
+        let is_rle = source
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .is_some_and(|line| line.starts_with('x') && line.contains('='));
+
+        if is_rle {
+            Self::parse_rle(source)
+        } else {
+            Self::parse_plaintext(source)
+        }
+    }
+
+    fn parse_plaintext(source: &str) -> Result<Self, PatternError> {
+        let mut pattern = Pattern::default();
+
+        let rows = source.lines().enumerate().filter(|(_, line)| !line.starts_with('!'));
+        for (y, (line_number, line)) in rows.enumerate() {
+            for (x, c) in line.trim_end().chars().enumerate() {
+                match c {
+                    '.' => {},
+                    'O' | 'o' | '*' => pattern.cells.push(uvec2(x as u32, y as u32)),
+                    _ => return Err(parse_error(line_number, format!("unexpected '{c}'"))),
+                }
+            }
+
+            pattern.size.x = pattern.size.x.max(line.trim_end().len() as u32);
+            pattern.size.y = y as u32 + 1;
+        }
+
+        Ok(pattern)
+    }
+
+    fn parse_rle(source: &str) -> Result<Self, PatternError> {
+        let mut pattern = Pattern::default();
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+
+        let (header_line, header) = lines.next().unwrap_or_default();
+        // Some rules, such as Larger than Life ones, contain commas, so fields without an `=` carry
+        // on the rule
+        let mut fields = Vec::<(&str, String)>::new();
+        for field in header.split(',') {
+            match (field.split_once('='), fields.last_mut()) {
+                (Some((key, value)), _) => fields.push((key.trim(), value.trim().to_string())),
+                (None, Some(("rule", rule))) => {
+                    rule.push(',');
+                    rule.push_str(field.trim());
+                },
+                (None, _) => return Err(parse_error(header_line, format!("malformed header field '{field}'"))),
+            }
+        }
+
+        for (key, value) in &fields {
+            let parse_size = |value: &str| value
+                .parse::<u32>()
+                .map_err(|_| parse_error(header_line, format!("invalid size '{value}'")));
+
+            match *key {
+                "x" => pattern.size.x = parse_size(value)?,
+                "y" => pattern.size.y = parse_size(value)?,
+                "rule" => {
+                    // Anything else is checked against the rule table it names when it's run
+                    pattern.rule = value.parse().ok();
+                    pattern.rule_name = Some(value.to_string());
+                },
+                _ => {},
+            }
+        }
+
+        let mut position = UVec2::ZERO;
+        let mut run = None::<u32>;
+        // Multi-state RLE writes states past 24 as a prefix from 'p' to 'y' before the letter
+        let mut prefix = None::<u32>;
+
+        'lines: for (line_number, line) in lines {
+            for c in line.trim().chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    run = Some(run.unwrap_or(0) * 10 + digit);
+                    continue;
+                }
+                if let 'p'..='y' = c {
+                    prefix = Some(c as u32 - 'p' as u32 + 1);
+                    continue;
+                }
+                let count = run.take().unwrap_or(1);
+
+                let state = match (prefix.take(), c) {
+                    (None, 'b' | '.') => 0,
+                    (None, 'o') => 1,
+                    (prefix, 'A'..='X') => prefix.unwrap_or(0) * 24 + (c as u32 - 'A' as u32 + 1),
+                    (None, '$') => {
+                        position = uvec2(0, position.y + count);
+                        continue;
+                    },
+                    (None, '!') => break 'lines,
+                    (None, c) if c.is_whitespace() => continue,
+                    _ => return Err(parse_error(line_number, format!("unexpected '{c}'"))),
+                };
+
+                let state = u8::try_from(state).map_err(|_| parse_error(line_number, format!("state {state} is too large")))?;
+                if state != 0 {
+                    pattern.cells.extend((0..count).map(|i| position + uvec2(i, 0)));
+                    pattern.states.extend(std::iter::repeat_n(state, count as usize));
+                }
+                position.x += count;
+            }
+        }
+
+        // Keep two-state patterns simple
+        if pattern.states.iter().all(|&state| state == 1) {
+            pattern.states.clear();
+        }
+
+        // Trust the cells over a header which is too small
+        for cell in &pattern.cells {
+            pattern.size = pattern.size.max(*cell + 1);
+        }
+
+        Ok(pattern)
+    }
+
+    /// Builds a pattern from cells which have already been moved to start at the origin
+    pub fn from_cells(cells: &[IVec2], rule: Option<Rule>) -> Self {
+        let cells = cells.iter().map(|cell| cell.as_uvec2()).collect::<Vec<_>>();
+        Pattern {
+            size: cells.iter().map(|&cell| cell + 1).fold(UVec2::ZERO, UVec2::max),
+            cells,
+            rule,
+            ..Default::default()
+        }
+    }
+
+    /// Collects the live cells from rows of packed words, `width` cells of `cell_bits` each, cropped
+    /// to their bounding box. Also returns where the box's top left corner was, or `None` for an
+    /// empty world
+    pub fn from_packed(words: &[u32], width: u32, cell_bits: u32) -> (Self, Option<UVec2>) {
+        let cells_per_word = u32::BITS / cell_bits;
+        let row_words = width.div_ceil(cells_per_word) as usize;
+        let mut cells = Vec::new();
+        let mut states = Vec::new();
+
+        for (y, row) in words.chunks_exact(row_words).enumerate() {
+            for (i, &word) in row.iter().enumerate() {
+                let mut remaining = word;
+                while remaining != 0 {
+                    // Most significant bits are the left-most cell
+                    let cell = remaining.leading_zeros() / cell_bits;
+                    let shift = u32::BITS - cell_bits * (cell + 1);
+                    let state = (remaining >> shift) & ((1 << cell_bits) - 1);
+                    remaining &= !(state << shift);
+
+                    cells.push(uvec2(i as u32 * cells_per_word + cell, y as u32));
+                    states.push(state as u8);
+                }
+            }
+        }
+
+        let Some(min) = cells.iter().copied().reduce(UVec2::min) else {
+            return (Pattern::default(), None);
+        };
+        let max = cells.iter().copied().fold(min, UVec2::max);
+
+        for cell in &mut cells {
+            *cell -= min;
+        }
+        if states.iter().all(|&state| state == 1) {
+            states.clear();
+        }
+
+        (Pattern { size: max - min + 1, cells, states, ..Default::default() }, Some(min))
+    }
+
+    /// State of the `i`th cell
+    pub fn state(&self, i: usize) -> u8 {
+        self.states.get(i).copied().unwrap_or(1)
+    }
+
+    /// Live cells and their states in reading order
+    fn sorted_cells(&self) -> Vec<(UVec2, u8)> {
+        let mut cells = self.cells.iter().enumerate().map(|(i, &cell)| (cell, self.state(i))).collect::<Vec<_>>();
+        cells.sort_unstable_by_key(|(cell, _)| (cell.y, cell.x));
+        cells.dedup_by_key(|(cell, _)| *cell);
+        cells
+    }
+
+    /// FNV-1a of the size, cells and any states. Doesn't depend on where the pattern was in the
+    /// world, so equal patterns hash the same
+    pub fn hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+
+        let cells = self.sorted_cells();
+        let states = cells.iter().map(|&(_, state)| state).filter(|_| !self.states.is_empty());
+
+        std::iter::once(self.size)
+            .chain(cells.iter().map(|&(cell, _)| cell))
+            .flat_map(|v| v.to_array())
+            .flat_map(u32::to_le_bytes)
+            .chain(states)
+            .fold(OFFSET_BASIS, |hash, byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+    }
+
+    pub fn to_rle(&self) -> String {
+        const MAX_LINE: usize = 70;
+
+        // Two-state patterns use `b` and `o`, others `.` and letters
+        let multistate = !self.states.is_empty();
+        let tag = |state: u8| match (multistate, state) {
+            (false, 0) => "b".to_string(),
+            (false, _) => "o".to_string(),
+            (true, 0) => ".".to_string(),
+            (true, state @ 1..=24) => char::from(b'A' + state - 1).to_string(),
+            (true, state) => {
+                let prefix = char::from(b'p' + (state - 25) / 24);
+                format!("{prefix}{}", char::from(b'A' + (state - 25) % 24))
+            },
+        };
+
+        let mut tokens = Vec::new();
+        let mut push = |count: u32, tag: &str| if count > 1 {
+            tokens.push(format!("{count}{tag}"));
+        } else if count == 1 {
+            tokens.push(tag.to_string());
+        };
+
+        // Position after the last written cell, and the run of cells in one state not yet written
+        let mut position = UVec2::ZERO;
+        let mut run = 0;
+        let mut run_state = 0;
+
+        for (cell, state) in self.sorted_cells() {
+            if cell.y == position.y && cell.x == position.x + run && state == run_state {
+                run += 1;
+                continue;
+            }
+
+            push(run, &tag(run_state));
+            position.x += run;
+            if cell.y != position.y {
+                push(cell.y - position.y, "$");
+                position = uvec2(0, cell.y);
+            }
+            push(cell.x - position.x, &tag(0));
+            position.x = cell.x;
+            run = 1;
+            run_state = state;
+        }
+        push(run, &tag(run_state));
+        push(1, "!");
+
+        let mut rle = format!("x = {}, y = {}", self.size.x, self.size.y);
+        if let Some(rule) = self.rule {
+            rle += &format!(", rule = {rule}");
+        } else if let Some(name) = &self.rule_name {
+            rle += &format!(", rule = {name}");
+        }
+        rle.push('\n');
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length + token.len() > MAX_LINE {
+                rle.push('\n');
+                line_length = 0;
+            }
+            line_length += token.len();
+            rle += &token;
+        }
+        rle.push('\n');
+
+        rle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    #[test]
+    fn rle_round_trip() {
+        let glider = Pattern::parse(GLIDER).unwrap();
+        assert_eq!(glider.size, uvec2(3, 3));
+        assert_eq!(glider.rule, Some(Rule::default()));
+        assert_eq!(glider.cells, [uvec2(1, 0), uvec2(2, 1), uvec2(0, 2), uvec2(1, 2), uvec2(2, 2)]);
+        assert_eq!(glider.to_rle(), GLIDER);
+
+        // Run counts can be split over lines, and anything after the `!` is ignored
+        let split = Pattern::parse("#N Glider\nx = 0, y = 0, rule = B3/S23\nbo$\n2\nbo$\n3o!\n2$12o\n").unwrap();
+        assert_eq!(split.sorted_cells(), glider.sorted_cells());
+        assert_eq!(split.to_rle(), GLIDER);
+
+        let row = Pattern::parse("x = 12, y = 1\n1\n2o!").unwrap();
+        assert_eq!(row.cells.len(), 12);
+        assert_eq!(row.to_rle(), "x = 12, y = 1\n12o!\n");
+    }
+
+    #[test]
+    fn multistate_round_trip() {
+        let rle = "x = 4, y = 2, rule = WireWorld\n.AB$C2.pA!\n";
+        let pattern = Pattern::parse(rle).unwrap();
+        assert_eq!(pattern.rule, None);
+        assert_eq!(pattern.rule_name.as_deref(), Some("WireWorld"));
+        assert_eq!(pattern.states, [1, 2, 3, 25]);
+        assert_eq!(pattern.to_rle(), rle);
+    }
+
+    #[test]
+    fn cells_round_trip() {
+        let glider = Pattern::parse("!Name: Glider\n.O.\n..O\nOOO\n").unwrap();
+        assert_eq!(glider.size, uvec2(3, 3));
+        assert_eq!(glider.sorted_cells(), Pattern::parse(GLIDER).unwrap().sorted_cells());
+
+        let rle = Pattern::parse(&glider.to_rle()).unwrap();
+        assert_eq!(rle.size, glider.size);
+        assert_eq!(rle.sorted_cells(), glider.sorted_cells());
+    }
+
+    #[test]
+    fn packed() {
+        // A glider a cell in and a row down in a 64 cell wide world, the left-most cell in the most
+        // significant bit
+        let words = [0, 0, 1 << 29, 0, 1 << 28, 0, 0b111 << 28, 0];
+        let (pattern, corner) = Pattern::from_packed(&words, 64, 1);
+        assert_eq!(corner, Some(uvec2(1, 1)));
+        assert_eq!(pattern.to_rle(), "x = 3, y = 3\nbo$2bo$3o!\n");
+
+        assert_eq!(Pattern::from_packed(&[0; 4], 64, 1).1, None);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Pattern::parse("x = 3, y = 3\nbz!").is_err());
+        assert!(Pattern::parse("x = three, y = 3\nbo!").is_err());
+        assert!(Pattern::parse(".O.\n.X.").is_err());
+    }
+}
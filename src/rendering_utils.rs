@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{cell::Cell, sync::{atomic::{AtomicU8, Ordering}, Arc}, time::{Duration, Instant}};
 
 use bounded_vec_deque::BoundedVecDeque;
 use bytemuck::Pod;
@@ -85,96 +85,242 @@ impl<T> SimpleUniformHelper<T> where T: Pod {
     }
 }
 
-pub struct Profiler {
-    set: wgpu::QuerySet,
+/// How many frames of timestamps can be waiting to be read back at once
+const READBACK_SLOTS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SlotState {
+    Free,
+    /// A resolve has been recorded but not yet submitted
+    Recorded,
+    /// Waiting on `map_async`
+    Mapping,
+}
+
+/// What a slot's `map_async` has come back with so far
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+/// The frame's timestamps are lost, and the slot is freed without them
+const MAP_FAILED: u8 = 2;
+
+/// One set of buffers for reading back a single frame's timestamps
+struct ReadbackSlot {
     resolve_buffer: wgpu::Buffer,
     destination_buffer: wgpu::Buffer,
+    state: SlotState,
+    /// Operations which were timed in the frame held by this slot
+    written: u64,
+    resolved_at: Instant,
+    /// One of `MAP_PENDING`, `MAP_DONE` or `MAP_FAILED`
+    mapped: Arc<AtomicU8>,
+}
+
+/// Absolute GPU timing of a single operation
+pub struct GpuSpan {
+    pub label: &'static str,
+    /// Time since the GPU's (arbitrary) timestamp origin
+    pub start: Duration,
+    pub end: Duration,
+    /// When the frame containing this span was handed to the profiler
+    pub submitted: Instant,
+}
+
+pub struct Profiler {
+    set: wgpu::QuerySet,
+    labels: Vec<&'static str>,
+    /// Results are harvested a few frames after being resolved so that the CPU never waits on the GPU
+    slots: Vec<ReadbackSlot>,
+    next_slot: usize,
     /// Number of different things which are being timed
     operations: usize,
+    /// Operations which have handed out timestamp writes since the last resolve, as not every
+    /// pass runs every frame
+    written: Cell<u64>,
     /// How many invocations should be used to get an averaged result
     samples: Vec<BoundedVecDeque<u64>>,
+    /// Spans which have been read back but not yet collected with [`Profiler::take_timeline`]
+    timeline: Vec<GpuSpan>,
     period: f32
 }
 
 impl Profiler {
-    pub fn new(operations: usize, frame_count: usize, device: &Device, period: f32) -> Self {
+    pub fn new(labels: &[&'static str], frame_count: usize, device: &Device, period: f32) -> Self {
+        let operations = labels.len();
         let buffer_size = operations * 2;
         assert!(buffer_size < wgpu::QUERY_SET_MAX_QUERIES as usize, "Maximum set queries exceeded");
+        assert!(operations <= u64::BITS as usize, "Too many operations");
         
         Self {
             operations,
+            labels: labels.to_vec(),
+            written: Cell::new(0),
             set: device.create_query_set(&wgpu::QuerySetDescriptor {
                 label: None,
                 ty: wgpu::QueryType::Timestamp,
                 count: buffer_size as u32,
             }),
-            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                mapped_at_creation: false,
-                size: size_of::<u64>() as u64 * buffer_size as u64,
-                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::QUERY_RESOLVE
-            }),
-            destination_buffer: device.create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                mapped_at_creation: false,
-                size: size_of::<u64>() as u64 * buffer_size as u64,
-                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ
-            }),
+            slots: (0..READBACK_SLOTS)
+                .map(|_| ReadbackSlot {
+                    resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: None,
+                        mapped_at_creation: false,
+                        size: size_of::<u64>() as u64 * buffer_size as u64,
+                        usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::QUERY_RESOLVE
+                    }),
+                    destination_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: None,
+                        mapped_at_creation: false,
+                        size: size_of::<u64>() as u64 * buffer_size as u64,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ
+                    }),
+                    state: SlotState::Free,
+                    written: 0,
+                    resolved_at: Instant::now(),
+                    mapped: Arc::new(AtomicU8::new(MAP_PENDING)),
+                })
+                .collect(),
+            next_slot: 0,
             samples: (0..operations)
                 .map(|_| BoundedVecDeque::with_capacity(frame_count, frame_count))
                 .collect::<Vec<_>>(),
+            timeline: Vec::new(),
             period
         }
     }
 
-    /// can only be called once all operations have finished
-    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+    /// can only be called once all operations have finished.
+    /// Skips the frame if every readback slot is still in flight
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let written = self.written.replace(0);
+        let slot = &mut self.slots[self.next_slot];
+        if slot.state != SlotState::Free || written == 0 {
+            return;
+        }
+
         encoder.resolve_query_set(
             &self.set,
             0..self.operations as u32 * 2,
-            &self.resolve_buffer,
+            &slot.resolve_buffer,
             0
         );
         encoder.copy_buffer_to_buffer(
-            &self.resolve_buffer,
+            &slot.resolve_buffer,
             0,
-            &self.destination_buffer,
+            &slot.destination_buffer,
             0,
-            self.resolve_buffer.size()
+            slot.resolve_buffer.size()
         );
+        slot.state = SlotState::Recorded;
+        slot.written = written;
+        slot.resolved_at = Instant::now();
     }
 
+    /// Must be called after the encoder passed to [`Profiler::resolve`] has been submitted.
+    /// Never blocks, results only show up once the GPU has caught up
     pub fn process_results(&mut self, device: &wgpu::Device) {
-        self.destination_buffer
-            .slice(..)
-            .map_async(wgpu::MapMode::Read, |_| ());
-
-        device.poll(wgpu::Maintain::wait()).panic_on_timeout();
+        let slot = &mut self.slots[self.next_slot];
+        if slot.state == SlotState::Recorded {
+            let mapped = slot.mapped.clone();
+            slot.destination_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    mapped.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+                });
+            slot.state = SlotState::Mapping;
+            self.next_slot = (self.next_slot + 1) % self.slots.len();
+        }
 
-        let view = self.destination_buffer
-            .slice(..)
-            .get_mapped_range();
+        device.poll(wgpu::Maintain::Poll);
+        self.harvest();
+    }
 
-        let data: Vec<u64> = bytemuck::cast_slice(&view).to_vec();
-        drop(view);
-        self.destination_buffer.unmap();
+    /// Blocks until every submitted frame has been read back
+    pub fn flush(&mut self, device: &wgpu::Device) {
+        self.process_results(device);
 
-        for (i, chunk) in data.chunks_exact(2).enumerate() {
-            let [start, stop] = chunk.try_into().unwrap();
-            self.samples[i].push_front(stop - start);
+        if self.slots.iter().any(|slot| slot.state == SlotState::Mapping) {
+            device.poll(wgpu::Maintain::Wait);
+            self.harvest();
         }
+    }
+
+    fn harvest(&mut self) {
+        // Oldest first so that samples stay in order
+        for i in 0..self.slots.len() {
+            let slot = &mut self.slots[(self.next_slot + i) % READBACK_SLOTS];
+            if slot.state != SlotState::Mapping {
+                continue;
+            }
+            match slot.mapped.swap(MAP_PENDING, Ordering::Acquire) {
+                MAP_PENDING => continue,
+                MAP_FAILED => {
+                    slot.state = SlotState::Free;
+                    continue;
+                },
+                _ => {},
+            }
+
+            let view = slot.destination_buffer
+                .slice(..)
+                .get_mapped_range();
 
+            let data: Vec<u64> = bytemuck::cast_slice(&view).to_vec();
+            drop(view);
+            slot.destination_buffer.unmap();
+            slot.state = SlotState::Free;
+
+            for (i, chunk) in data.chunks_exact(2).enumerate() {
+                if slot.written & (1 << i) == 0 {
+                    continue;
+                }
+                let [start, stop] = chunk.try_into().unwrap();
+                self.samples[i].push_front(stop.wrapping_sub(start));
+                self.timeline.push(GpuSpan {
+                    label: self.labels[i],
+                    start: Duration::from_secs_f64(start as f64 * self.period as f64 * 1e-9),
+                    end: Duration::from_secs_f64(stop as f64 * self.period as f64 * 1e-9),
+                    submitted: slot.resolved_at,
+                });
+            }
+        }
     }
 
     pub fn average_time_raw(&self, idx: usize) -> u64 {
-        self.samples[idx].iter().sum::<u64>() / self.samples[idx].len() as u64
+        self.samples[idx].iter().sum::<u64>() / (self.samples[idx].len() as u64).max(1)
+    }
+
+    fn to_duration(&self, raw: u64) -> Duration {
+        Duration::from_secs_f64(raw as f64 * self.period as f64 * 1e-9)
     }
 
     pub fn average_time(&self, idx: usize) -> Duration {
-        Duration::from_secs_f64(self.average_time_raw(idx) as f64 * self.period as f64 * 1e-9)
+        self.to_duration(self.average_time_raw(idx))
+    }
+
+    pub fn min_time(&self, idx: usize) -> Duration {
+        self.to_duration(self.samples[idx].iter().copied().min().unwrap_or_default())
+    }
+
+    pub fn max_time(&self, idx: usize) -> Duration {
+        self.to_duration(self.samples[idx].iter().copied().max().unwrap_or_default())
+    }
+
+    /// Recorded times, oldest first
+    pub fn samples(&self, idx: usize) -> impl Iterator<Item = Duration> + '_ {
+        self.samples[idx].iter().rev().map(|&raw| self.to_duration(raw))
+    }
+
+    /// Spans read back since the last call, in the order they were harvested
+    pub fn take_timeline(&mut self) -> Vec<GpuSpan> {
+        std::mem::take(&mut self.timeline)
+    }
+
+    fn mark_written(&self, idx: u32) {
+        self.written.set(self.written.get() | (1 << idx));
     }
 
-    pub fn compute_pass_timestamp_writes(&self, idx: u32) -> wgpu::ComputePassTimestampWrites {
+    pub fn compute_pass_timestamp_writes(&self, idx: u32) -> wgpu::ComputePassTimestampWrites<'_> {
+        self.mark_written(idx);
         let idx = idx * 2;
         wgpu::ComputePassTimestampWrites {
             query_set: &self.set,
@@ -183,7 +329,8 @@ impl Profiler {
         }
     }
 
-    pub fn render_pass_timestamp_writes(&self, idx: u32) -> wgpu::RenderPassTimestampWrites {
+    pub fn render_pass_timestamp_writes(&self, idx: u32) -> wgpu::RenderPassTimestampWrites<'_> {
+        self.mark_written(idx);
         let idx = idx * 2;
         wgpu::RenderPassTimestampWrites {
             query_set: &self.set,
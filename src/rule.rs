@@ -0,0 +1,877 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use naga_oil::compose::ShaderDefValue;
+
+use crate::{hensel::{self, NeighbourSet}, rule_table::MAX_STATES, world};
+
+/// Whether a cell is alive next generation for each of the 512 arrangements of its 3x3
+/// neighbourhood, bit `i % 32` of word `i / 32` being for the arrangement `i`. The bits of an
+/// arrangement from most to least significant are NW, N, NE, W, the cell itself, E, SW, S and SE
+pub type NeighbourhoodTable = [u32; 16];
+
+/// Bit of a neighbourhood arrangement for each neighbour, clockwise from north
+const NEIGHBOUR_BITS: [u32; 8] = [7, 6, 3, 0, 1, 2, 5, 8];
+const CENTRE_BIT: u32 = 4;
+
+/// Alphabet of MAP rule strings, which give the table of every neighbourhood six bits at a time
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Characters needed for all 512 neighbourhoods
+const MAP_LENGTH: usize = 512usize.div_ceil(6);
+
+/// Cells around the centre of a 3x3 neighbourhood which count as its neighbours
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Neighbourhood {
+    #[default]
+    Moore,
+    /// Only the orthogonal neighbours, written with a `V` suffix
+    VonNeumann,
+    /// All but the north east and south west neighbours, which is a hexagonal grid once each row is
+    /// moved half a cell along from the one above. Written with an `H` suffix
+    Hexagonal,
+}
+
+impl Neighbourhood {
+    /// Neighbours included, clockwise from north
+    fn neighbours(self) -> u8 {
+        match self {
+            Neighbourhood::Moore => 0b11111111,
+            Neighbourhood::VonNeumann => 0b01010101,
+            Neighbourhood::Hexagonal => 0b11011101,
+        }
+    }
+
+    /// Bits of a neighbourhood arrangement which are counted
+    fn arrangement_mask(self) -> u32 {
+        Rule::arrangement(self.neighbours(), false)
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Neighbourhood::Moore => "",
+            Neighbourhood::VonNeumann => "V",
+            Neighbourhood::Hexagonal => "H",
+        }
+    }
+}
+
+/// How a rule decides each cell's next state. Each kind has its own notation and shader
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RuleKind {
+    /// Counts the live neighbours, such as `B3/S23`. Bit `n` of each mask is set when `n` live
+    /// neighbours cause a birth / survival
+    Totalistic {
+        birth: u16,
+        survival: u16,
+        /// Which cells are counted. Anything but the Moore neighbourhood runs as a table
+        neighbourhood: Neighbourhood,
+        /// States including dead. Above two this is a Generations rule, such as Brian's Brain
+        /// (`B2/S/C3`), where live cells which don't survive pass through the states after 1 before
+        /// dying, and only state 1 counts as a live neighbour
+        states: u32,
+    },
+    /// Depends on where the neighbours are rather than only how many there are, such as `B2-a/S12`
+    /// in Hensel notation or a MAP string
+    Table {
+        table: NeighbourhoodTable,
+        /// States including dead, as for totalistic rules
+        states: u32,
+    },
+    /// Life where live cells have one of `colours` colours, each its own state after dead:
+    /// Immigration with two colours or QuadLife with four. Cells are born with the colour most of
+    /// their three parents have, and QuadLife cells with three parents of different colours take the
+    /// fourth
+    Multicolour {
+        colours: u32,
+    },
+    LargerThanLife(LargerThanLife),
+    OneDimensional(OneDimensional),
+    Margolus(Margolus),
+}
+
+/// A cellular automaton rule, parsed from and written as a rule string
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rule {
+    pub kind: RuleKind,
+}
+
+impl Rule {
+    pub const LIFE: Rule = Rule::totalistic(1 << 3, (1 << 2) | (1 << 3), 2);
+
+    const fn totalistic(birth: u16, survival: u16, states: u32) -> Self {
+        Self { kind: RuleKind::Totalistic { birth, survival, neighbourhood: Neighbourhood::Moore, states } }
+    }
+
+    /// Whether a cell with the arrangement of neighbourhood `neighbourhood` is alive next generation.
+    /// Always false for rules which don't run on the 3x3 neighbourhood
+    pub fn next(&self, neighbourhood: u32) -> bool {
+        match &self.kind {
+            RuleKind::Table { table, .. } => table[neighbourhood as usize / 32] & (1 << (neighbourhood % 32)) != 0,
+            RuleKind::Totalistic { neighbourhood: counted, .. } => {
+                let (birth, survival) = self.masks();
+                let alive = neighbourhood & (1 << CENTRE_BIT) != 0;
+                let mask = if alive { survival } else { birth };
+                mask & (1 << (neighbourhood & counted.arrangement_mask()).count_ones()) != 0
+            },
+            RuleKind::Multicolour { .. } => Self::LIFE.next(neighbourhood),
+            RuleKind::LargerThanLife(_) | RuleKind::OneDimensional(_) | RuleKind::Margolus(_) => false,
+        }
+    }
+
+    /// State of a multi-colour rule's cell next generation, from its own state followed by its eight
+    /// neighbours'. Works like the shader, with newborn cells taking the colour most of their parents
+    /// have, or with three parents of different colours the one none of them have
+    pub fn next_colour(&self, neighbourhood: [u32; 9]) -> u32 {
+        let colours = self.states() - 1;
+        let mut parents = [0; 4];
+        for &colour in neighbourhood[1..].iter().filter(|&&colour| colour != 0) {
+            parents[colour as usize - 1] += 1;
+        }
+        let neighbours = parents.iter().sum::<u32>();
+
+        let (birth, survival) = self.masks();
+        let state = neighbourhood[0];
+        if state != 0 {
+            return if survival & (1 << neighbours) != 0 { state } else { 0 };
+        }
+        if birth & (1 << neighbours) == 0 {
+            return 0;
+        }
+
+        (0..colours)
+            .find(|&colour| parents[colour as usize] * 2 > neighbours)
+            .or_else(|| (0..colours).find(|&colour| parents[colour as usize] == 0))
+            .map_or(1, |colour| colour + 1)
+    }
+
+    /// Birth and survival masks of totalistic rules, which the shader counts neighbours against.
+    /// Empty for every other kind of rule
+    pub fn masks(&self) -> (u16, u16) {
+        match self.kind {
+            RuleKind::Totalistic { birth, survival, .. } => (birth, survival),
+            RuleKind::Multicolour { .. } => Self::LIFE.masks(),
+            _ => (0, 0),
+        }
+    }
+
+    /// Which cells around the centre of a 3x3 neighbourhood are counted. Everything but totalistic
+    /// rules looks at all of them, or at more than the 3x3 neighbourhood
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        match self.kind {
+            RuleKind::Totalistic { neighbourhood, .. } => neighbourhood,
+            _ => Neighbourhood::Moore,
+        }
+    }
+
+    /// Whether the rule has to look up each neighbourhood rather than count neighbours
+    pub fn uses_table(&self) -> bool {
+        matches!(self.kind, RuleKind::Table { .. }) || self.neighbourhood() != Neighbourhood::Moore
+    }
+
+    /// The rule as a table, even if it's totalistic
+    pub fn table(&self) -> NeighbourhoodTable {
+        let mut table = NeighbourhoodTable::default();
+        for neighbourhood in (0..512).filter(|&n| self.next(n)) {
+            table[neighbourhood as usize / 32] |= 1 << (neighbourhood % 32);
+        }
+        table
+    }
+
+    /// Arrangement of a cell's neighbourhood from its neighbours, clockwise from north
+    fn arrangement(neighbours: u8, alive: bool) -> u32 {
+        (0..8)
+            .filter(|i| neighbours & (1 << i) != 0)
+            .fold((alive as u32) << CENTRE_BIT, |neighbourhood, i| neighbourhood | (1 << NEIGHBOUR_BITS[i]))
+    }
+
+    /// Arrangements of neighbours causing a birth / survival
+    fn neighbour_set(&self, alive: bool) -> NeighbourSet {
+        let mut set = NeighbourSet::default();
+        for neighbours in (0..=u8::MAX).filter(|&n| self.next(Self::arrangement(n, alive))) {
+            set[neighbours as usize / 64] |= 1 << (neighbours % 64);
+        }
+        set
+    }
+
+    /// Only keeps a table when the rule can't be written with masks
+    fn from_neighbour_sets(birth: &NeighbourSet, survival: &NeighbourSet, states: u32) -> Self {
+        if let (Some(birth), Some(survival)) = (hensel::totalistic(birth), hensel::totalistic(survival)) {
+            return Self::totalistic(birth, survival, states);
+        }
+
+        let mut table = NeighbourhoodTable::default();
+        for neighbours in 0..=u8::MAX {
+            for (alive, set) in [(false, birth), (true, survival)] {
+                if hensel::contains(set, neighbours) {
+                    let neighbourhood = Self::arrangement(neighbours, alive);
+                    table[neighbourhood as usize / 32] |= 1 << (neighbourhood % 32);
+                }
+            }
+        }
+
+        Self { kind: RuleKind::Table { table, states } }
+    }
+
+    /// Whether the rule only depends on the 3x3 neighbourhood of two state cells, so that
+    /// [`Rule::next`] can step it
+    pub fn is_two_state_3x3(&self) -> bool {
+        matches!(self.kind, RuleKind::Totalistic { states: 2, .. } | RuleKind::Table { states: 2, .. })
+    }
+
+    /// Whether the rule can be made second order, each generation being XORed with the one before.
+    /// That needs two states and every cell being updated the same way every generation, which
+    /// rules out Margolus rules as their blocks alternate
+    pub fn supports_second_order(&self) -> bool {
+        match self.kind {
+            RuleKind::Totalistic { states, .. } | RuleKind::Table { states, .. } => states == 2,
+            RuleKind::LargerThanLife(larger_than_life) => larger_than_life.states == 2,
+            RuleKind::Multicolour { .. } | RuleKind::OneDimensional(_) | RuleKind::Margolus(_) => false,
+        }
+    }
+
+    /// Whether cells can be left out of updates at random, which needs every cell to be updated on its
+    /// own rather than a row or block at a time
+    pub fn supports_asynchronous(&self) -> bool {
+        !matches!(self.kind, RuleKind::OneDimensional(_) | RuleKind::Margolus(_))
+    }
+
+    /// Whether births and survivals can be left to chance, which also needs two states
+    pub fn supports_stochastic(&self) -> bool {
+        self.supports_asynchronous() && self.states() == 2
+    }
+
+    /// Whether cells are decided by their count of live Moore neighbours alone, which a
+    /// [`crate::RuleShader`] can then take over
+    pub fn supports_rule_shader(&self) -> bool {
+        matches!(self.kind, RuleKind::Totalistic { neighbourhood: Neighbourhood::Moore, .. } | RuleKind::Multicolour { .. })
+    }
+
+    /// States including dead, each colour of a multi-colour rule being its own state
+    pub fn states(&self) -> u32 {
+        match self.kind {
+            RuleKind::Totalistic { states, .. } | RuleKind::Table { states, .. } => states,
+            RuleKind::LargerThanLife(larger_than_life) => larger_than_life.states,
+            RuleKind::Multicolour { colours } => colours + 1,
+            RuleKind::OneDimensional(_) | RuleKind::Margolus(_) => 2,
+        }
+    }
+
+    /// Whether live cells which don't survive pass through more states before dying
+    pub fn is_generations(&self) -> bool {
+        !matches!(self.kind, RuleKind::Multicolour { .. }) && self.states() > 2
+    }
+
+    pub fn cell_bits(&self) -> u32 {
+        world::cell_bits(self.states())
+    }
+
+    /// sRGB colour of every state. Dying states fade from orange towards black, and the colours of
+    /// multi-colour rules are red, green, blue and yellow
+    pub fn colours(&self) -> Vec<[u8; 3]> {
+        if let RuleKind::Multicolour { colours } = self.kind {
+            let live = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]];
+            return [[0, 0, 0]].into_iter().chain(live.into_iter().take(colours as usize)).collect();
+        }
+
+        let states = self.states();
+        let dying = (2..states).map(|state| {
+            let brightness = (states - state) as f32 / (states - 1) as f32;
+            [255., 160., 64.].map(|channel: f32| (channel * brightness).round() as u8)
+        });
+
+        [[0, 0, 0], [255, 255, 255]].into_iter().chain(dying).collect()
+    }
+
+    /// A rule from a MAP string's table, which is written with masks if it's totalistic
+    fn from_table(table: NeighbourhoodTable, states: u32) -> Self {
+        let rule = Self { kind: RuleKind::Table { table, states } };
+        Self::from_neighbour_sets(&rule.neighbour_set(false), &rule.neighbour_set(true), states)
+    }
+
+    /// Table from the base64 part of a MAP rule string, the first neighbourhood being the most
+    /// significant bit of the first character
+    fn parse_map(map: &str) -> Result<NeighbourhoodTable, String> {
+        if map.len() != MAP_LENGTH {
+            return Err(format!("MAP rules should have {MAP_LENGTH} base64 characters, not {}", map.len()));
+        }
+
+        let mut table = NeighbourhoodTable::default();
+        for (i, c) in map.chars().enumerate() {
+            let sextet = BASE64
+                .iter()
+                .position(|&b| b as char == c)
+                .ok_or_else(|| format!("'{c}' isn't a base64 character"))?;
+
+            for bit in (0..6).filter(|bit| sextet & (0b100000 >> bit) != 0) {
+                let neighbourhood = i * 6 + bit;
+                if neighbourhood < 512 {
+                    table[neighbourhood / 32] |= 1 << (neighbourhood % 32);
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn write_map(&self) -> String {
+        let table = self.table();
+        let bit = |neighbourhood: usize| neighbourhood < 512 && table[neighbourhood / 32] & (1 << (neighbourhood % 32)) != 0;
+
+        (0..MAP_LENGTH)
+            .map(|i| {
+                let sextet = (0..6).fold(0, |sextet, b| (sextet << 1) | bit(i * 6 + b) as usize);
+                BASE64[sextet] as char
+            })
+            .collect()
+    }
+
+    fn parse_states(states: &str) -> Result<u32, String> {
+        match states.parse() {
+            Ok(states @ 2..=MAX_STATES) => Ok(states),
+            _ => Err(format!("'{states}' is not a number of states between 2 and {MAX_STATES}")),
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::LIFE
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// Accepts `B3/S23`, `b3s23` and the older survival first `23/3`, with Hensel notation letters
+    /// such as `B2-a/S12`. Generations rules add the number of states, as in `B2/S/C3`, `B2/S/G3`
+    /// or survival first `/2/3`. Any rule on the Moore neighbourhood can be given as a MAP string,
+    /// such as `MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA`
+    /// for Life, followed by `/C3` for a Generations rule. Larger than Life rules are written as
+    /// `R5,C0,M1,S34..58,B34..45,NM`, with more states than two after `C`, one dimensional rules as
+    /// `W30` or `T52,R2`, and Margolus
+    /// rules as the 16 entries of their table, such as `M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15`.
+    /// `Immigration` and `QuadLife` are the multi-colour variants of Life
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let lower = s.to_ascii_lowercase();
+
+        match lower.as_str() {
+            "immigration" => return Ok(Self { kind: RuleKind::Multicolour { colours: 2 } }),
+            "quadlife" => return Ok(Self { kind: RuleKind::Multicolour { colours: 4 } }),
+            _ => {},
+        }
+
+        if lower.starts_with('w') || (lower.starts_with('t') && lower.contains(',')) {
+            return OneDimensional::parse(&lower).map(OneDimensional::into_rule);
+        }
+
+        if lower.starts_with('m') && lower.contains(',') {
+            return Margolus::parse(&lower).map(Margolus::into_rule);
+        }
+
+        if lower.starts_with('r') && lower.contains(',') {
+            return LargerThanLife::parse(&lower).map(LargerThanLife::into_rule);
+        }
+
+        // Base64 is case sensitive, so MAP strings are read from the rule as it was written
+        if lower.starts_with("map") {
+            let body = &s[3..];
+            if !body.is_ascii() {
+                return Err(format!("Rule '{s}' isn't a valid MAP string"));
+            }
+
+            let (map, rest) = body.split_at(MAP_LENGTH.min(body.len()));
+            let states = match rest.trim_start_matches('=').to_ascii_lowercase().as_str() {
+                "" => 2,
+                rest => match rest.strip_prefix('/') {
+                    Some(states) => Self::parse_states(states.trim_start_matches(['c', 'g']))?,
+                    None => return Err(format!("Rule '{s}' has '{rest}' after its MAP string")),
+                },
+            };
+
+            return Ok(Self::from_table(Self::parse_map(map)?, states));
+        }
+
+        let (lower, neighbourhood) = match lower.strip_suffix(['v', 'h']) {
+            Some(rest) if lower.ends_with('v') => (rest, Neighbourhood::VonNeumann),
+            Some(rest) => (rest, Neighbourhood::Hexagonal),
+            None => (lower.as_str(), Neighbourhood::Moore),
+        };
+
+        let (birth, survival, states) = if let Some(rest) = lower.strip_prefix('b') {
+            let (birth, rest) = rest
+                .split_once('s')
+                .ok_or_else(|| format!("Rule '{s}' is missing the survival ('S') part"))?;
+            // `c` is also a Hensel letter, so the number of states has to come after a slash
+            let (survival, states) = match rest.rsplit_once('/') {
+                Some((survival, states)) if states.starts_with(['c', 'g']) => (survival, Some(&states[1..])),
+                _ => (rest, None),
+            };
+            (birth.trim_end_matches('/'), survival.trim_matches('/'), states)
+        } else {
+            match lower.split('/').collect::<Vec<_>>()[..] {
+                [survival, birth] => (birth, survival, None),
+                [survival, birth, states] => (birth, survival, Some(states)),
+                _ => return Err(format!("Rule '{s}' should look like B3/S23")),
+            }
+        };
+
+        let rule = Self::from_neighbour_sets(
+            &hensel::parse(birth)?,
+            &hensel::parse(survival)?,
+            states.map_or(Ok(2), Self::parse_states)?,
+        );
+
+        if neighbourhood == Neighbourhood::Moore {
+            return Ok(rule);
+        }
+        let RuleKind::Totalistic { birth, survival, states, .. } = rule.kind else {
+            return Err(format!("Rule '{s}' uses Hensel notation, which only works on the Moore neighbourhood"));
+        };
+
+        let size = neighbourhood.neighbours().count_ones();
+        if (birth | survival) >> (size + 1) != 0 {
+            return Err(format!("Rule '{s}' counts more than the {size} neighbours its neighbourhood has"));
+        }
+
+        Ok(Self { kind: RuleKind::Totalistic { birth, survival, neighbourhood, states } })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = |mask: u16| (0..=8)
+            .filter(|n| mask & (1 << n) != 0)
+            .map(|n| char::from_digit(n, 10).unwrap())
+            .collect::<String>();
+
+        let (birth, survival) = (self.neighbour_set(false), self.neighbour_set(true));
+        match self.kind {
+            RuleKind::LargerThanLife(larger_than_life) => return write!(f, "{larger_than_life}"),
+            RuleKind::OneDimensional(one_dimensional) => return write!(f, "{one_dimensional}"),
+            RuleKind::Margolus(margolus) => return write!(f, "{margolus}"),
+            RuleKind::Multicolour { colours: 4 } => return write!(f, "QuadLife"),
+            RuleKind::Multicolour { .. } => return write!(f, "Immigration"),
+            // Rules which aren't isotropic can't be written in Hensel notation
+            RuleKind::Table { .. } if !hensel::is_isotropic(&birth) || !hensel::is_isotropic(&survival) => write!(f, "MAP{}", self.write_map())?,
+            RuleKind::Table { .. } => write!(f, "B{}/S{}", hensel::write(&birth), hensel::write(&survival))?,
+            RuleKind::Totalistic { birth, survival, .. } => write!(f, "B{}/S{}", counts(birth), counts(survival))?,
+        }
+        if self.is_generations() {
+            write!(f, "/C{}", self.states())?;
+        }
+        write!(f, "{}", self.neighbourhood().suffix())
+    }
+}
+
+/// A rule which counts the live cells in a square reaching `range` cells out from each cell, such
+/// as Bosco's rule `R5,C0,M1,S34..58,B34..45,NM`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LargerThanLife {
+    pub range: u32,
+    /// States including dead, given by `C`. Above two, cells which don't survive pass through the
+    /// states after 1 before dying as in Generations rules, and only state 1 counts as live
+    pub states: u32,
+    /// Whether a cell counts towards its own neighbours
+    pub include_centre: bool,
+    /// Fewest and most neighbours causing a birth
+    pub birth: [u32; 2],
+    /// Fewest and most neighbours causing survival
+    pub survival: [u32; 2],
+}
+
+impl LargerThanLife {
+    /// Furthest a neighbourhood can reach, so that it never goes past the words either side
+    pub const MAX_RANGE: u32 = 16;
+
+    /// Parses lowercase fields such as `r5,c0,m1,s34..58,b34..45,nm`
+    fn parse(rule: &str) -> Result<Self, String> {
+        let interval = |value: &str| -> Result<[u32; 2], String> {
+            let (min, max) = value.split_once("..").unwrap_or((value, value));
+            match (min.parse(), max.parse()) {
+                (Ok(min), Ok(max)) if min <= max => Ok([min, max]),
+                _ => Err(format!("'{value}' should be a range of neighbour counts such as 34..58")),
+            }
+        };
+
+        let (mut range, mut birth, mut survival) = (None, None, None);
+        let mut include_centre = false;
+        let mut states = 2;
+
+        for field in rule.split(',').map(str::trim) {
+            let (key, value) = field.split_at(field.chars().next().map_or(0, char::len_utf8));
+            match key {
+                "r" => range = Some(value
+                    .parse()
+                    .ok()
+                    .filter(|range| (1..=Self::MAX_RANGE).contains(range))
+                    .ok_or_else(|| format!("'{value}' is not a range between 1 and {}", Self::MAX_RANGE))?),
+                // Golly writes two states as C0
+                "c" => states = match value {
+                    "0" | "1" | "2" => 2,
+                    states => Rule::parse_states(states)?,
+                },
+                "m" => include_centre = match value {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(format!("'{value}' should be 0 or 1 for whether cells count themselves")),
+                },
+                "s" => survival = Some(interval(value)?),
+                "b" => birth = Some(interval(value)?),
+                "n" if value == "m" => {},
+                "n" => return Err(format!("Larger than Life rules only support the Moore neighbourhood (NM), not N{}", value.to_ascii_uppercase())),
+                _ => return Err(format!("Unknown Larger than Life field '{field}'")),
+            }
+        }
+
+        Ok(Self {
+            range: range.ok_or("Larger than Life rules need a range such as R5")?,
+            states,
+            include_centre,
+            birth: birth.ok_or("Larger than Life rules need births such as B34..45")?,
+            survival: survival.ok_or("Larger than Life rules need survivals such as S34..58")?,
+        })
+    }
+
+    /// Range one rules are the same as totalistic rules, which run on the faster kernel
+    fn into_rule(self) -> Rule {
+        if self.range > 1 {
+            return Rule { kind: RuleKind::LargerThanLife(self) };
+        }
+
+        let mask = |[min, max]: [u32; 2], alive: bool| (min..=max)
+            .filter_map(|count| count.checked_sub((alive && self.include_centre) as u32))
+            .filter(|&neighbours| neighbours <= 8)
+            .fold(0, |mask, neighbours| mask | (1 << neighbours));
+
+        Rule::totalistic(mask(self.birth, false), mask(self.survival, true), self.states)
+    }
+
+    pub fn shader_defs(&self) -> HashMap<String, ShaderDefValue> {
+        let mut defs = HashMap::from([
+            ("LARGER_THAN_LIFE".into(), ShaderDefValue::Bool(true)),
+            ("RANGE".into(), ShaderDefValue::UInt(self.range)),
+            ("BIRTH_MIN".into(), ShaderDefValue::UInt(self.birth[0])),
+            ("BIRTH_MAX".into(), ShaderDefValue::UInt(self.birth[1])),
+            ("SURVIVAL_MIN".into(), ShaderDefValue::UInt(self.survival[0])),
+            ("SURVIVAL_MAX".into(), ShaderDefValue::UInt(self.survival[1])),
+        ]);
+
+        if self.include_centre {
+            defs.insert("INCLUDE_CENTRE".into(), ShaderDefValue::Bool(true));
+        }
+
+        defs
+    }
+}
+
+impl fmt::Display for LargerThanLife {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [birth_min, birth_max] = self.birth;
+        let [survival_min, survival_max] = self.survival;
+        write!(
+            f,
+            "R{},C{},M{},S{survival_min}..{survival_max},B{birth_min}..{birth_max},NM",
+            self.range,
+            if self.states == 2 { 0 } else { self.states },
+            self.include_centre as u32,
+        )
+    }
+}
+
+/// A rule on a single row of cells, where each generation is written to the row below the one
+/// before so that the world shows the whole history. Elementary rules such as Wolfram's rule 30
+/// (`W30`) look at each cell and the cells either side of it, while totalistic rules such as
+/// `T52,R2` count the live cells within `range` of each cell, itself included
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OneDimensional {
+    pub range: u32,
+    pub totalistic: bool,
+    /// Bit `n` is set when the arrangement `n` of the cells in range, the left-most being the most
+    /// significant bit, is alive next generation. For totalistic rules it's set when `n` live cells are
+    pub code: u32,
+}
+
+impl OneDimensional {
+    /// Furthest a totalistic rule can reach, so that its code still fits in a word
+    pub const MAX_RANGE: u32 = 15;
+
+    /// Parses lowercase rules such as `w30` or `t52,r2`
+    fn parse(rule: &str) -> Result<Self, String> {
+        if let Some(code) = rule.strip_prefix('w') {
+            return match code.parse() {
+                Ok(code @ 0..=255) => Ok(Self { range: 1, totalistic: false, code }),
+                _ => Err(format!("'{code}' is not an elementary rule between 0 and 255")),
+            };
+        }
+
+        let (mut range, mut code) = (None, None);
+        for field in rule.split(',').map(str::trim) {
+            let (key, value) = field.split_at(field.chars().next().map_or(0, char::len_utf8));
+            match key {
+                "r" => range = Some(value
+                    .parse()
+                    .ok()
+                    .filter(|range| (1..=Self::MAX_RANGE).contains(range))
+                    .ok_or_else(|| format!("'{value}' is not a range between 1 and {}", Self::MAX_RANGE))?),
+                "t" => code = Some(value.parse::<u32>().map_err(|_| format!("'{value}' is not a totalistic rule code"))?),
+                _ => return Err(format!("Unknown one dimensional rule field '{field}'")),
+            }
+        }
+
+        let range = range.ok_or("Totalistic rules need a range such as R2")?;
+        let code = code.ok_or("Totalistic rules need a code such as T52")?;
+        // Counts go from none to every cell in range
+        let counts = 2 * range + 2;
+        if counts < u32::BITS && code >> counts != 0 {
+            return Err(format!("T{code} has a bit for more than the {} cells within range {range}", counts - 1));
+        }
+
+        Ok(Self { range, totalistic: true, code })
+    }
+
+    fn into_rule(self) -> Rule {
+        Rule { kind: RuleKind::OneDimensional(self) }
+    }
+
+    pub fn shader_defs(&self) -> HashMap<String, ShaderDefValue> {
+        let mut defs = HashMap::from([
+            ("ONE_DIMENSIONAL".into(), ShaderDefValue::Bool(true)),
+            ("ROW_RANGE".into(), ShaderDefValue::UInt(self.range)),
+            ("RULE_CODE".into(), ShaderDefValue::UInt(self.code)),
+        ]);
+
+        if self.totalistic {
+            defs.insert("TOTALISTIC".into(), ShaderDefValue::Bool(true));
+        }
+
+        defs
+    }
+}
+
+impl fmt::Display for OneDimensional {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.totalistic {
+            write!(f, "T{},R{}", self.code, self.range)
+        } else {
+            write!(f, "W{}", self.code)
+        }
+    }
+}
+
+/// A block rule on the Margolus neighbourhood, where the world is split into 2x2 blocks which each
+/// become a new block from a table. Blocks start one cell further right and down every other
+/// generation, so that information spreads. These are the usual rules for reversible computing,
+/// such as the billiard ball model (`M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15`), Critters
+/// (`M15,14,13,3,11,5,6,1,7,9,10,2,12,4,8,0`) and Tron (`M15,1,2,3,4,5,6,7,8,9,10,11,12,13,14,0`)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Margolus {
+    /// Block each block becomes. Blocks have a bit for each cell, 1 for the top left, 2 for the top
+    /// right, 4 for the bottom left and 8 for the bottom right
+    pub table: [u8; 16],
+}
+
+impl Margolus {
+    /// Parses lowercase rules such as `m0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15`
+    fn parse(rule: &str) -> Result<Self, String> {
+        let blocks = rule[1..]
+            .split(',')
+            .map(|block| match block.trim().parse() {
+                Ok(block @ 0..=15) => Ok(block),
+                _ => Err(format!("'{block}' is not a 2x2 block between 0 and 15")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let table = blocks
+            .try_into()
+            .map_err(|blocks: Vec<_>| format!("Margolus rules need a block for each of the 16 blocks, not {}", blocks.len()))?;
+        Ok(Self { table })
+    }
+
+    fn into_rule(self) -> Rule {
+        Rule { kind: RuleKind::Margolus(self) }
+    }
+
+    pub fn shader_defs(&self) -> HashMap<String, ShaderDefValue> {
+        // Four bits for each block, the first eight in one word and the rest in another
+        let pack = |blocks: &[u8]| blocks.iter().rev().fold(0, |word, &block| (word << 4) | block as u32);
+        HashMap::from([
+            ("MARGOLUS".into(), ShaderDefValue::Bool(true)),
+            ("MARGOLUS_LOW".into(), ShaderDefValue::UInt(pack(&self.table[..8]))),
+            ("MARGOLUS_HIGH".into(), ShaderDefValue::UInt(pack(&self.table[8..]))),
+        ])
+    }
+}
+
+impl fmt::Display for Margolus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let blocks = self.table.map(|block| block.to_string());
+        write!(f, "M{}", blocks.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIFE_MAP: &str = "ARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA";
+
+    #[test]
+    fn life_map() {
+        assert_eq!(Rule::parse_map(LIFE_MAP).unwrap(), Rule::LIFE.table());
+        assert_eq!(Rule::LIFE.write_map(), LIFE_MAP);
+        assert_eq!(format!("MAP{LIFE_MAP}").parse::<Rule>().unwrap().table(), Rule::LIFE.table());
+    }
+
+    #[test]
+    fn map_padding() {
+        assert_eq!(format!("MAP{LIFE_MAP}==").parse::<Rule>().unwrap().table(), Rule::LIFE.table());
+        assert_eq!(format!("MAP{LIFE_MAP}/3").parse::<Rule>().unwrap().states(), 3);
+    }
+
+    #[test]
+    fn invalid_map() {
+        assert!(Rule::parse_map(&LIFE_MAP[1..]).is_err());
+        assert!(Rule::parse_map(&format!("{LIFE_MAP}A")).is_err());
+        assert!(Rule::parse_map(&LIFE_MAP.replace('R', "*")).is_err());
+        assert!(format!("MAP{}", &LIFE_MAP[..80]).parse::<Rule>().is_err());
+        assert!(format!("MAP{LIFE_MAP}x").parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn generations() {
+        for rule in ["B2/S/C3", "B2/S345/C4", "B3/S23/C8", "B2-a/S12/C5"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+
+        // Golly's survival first form, as for Brian's Brain and Star Wars
+        assert_eq!("/2/3".parse::<Rule>().unwrap().to_string(), "B2/S/C3");
+        assert_eq!("345/2/4".parse::<Rule>().unwrap().to_string(), "B2/S345/C4");
+        assert_eq!("b2/s/g3".parse::<Rule>().unwrap().to_string(), "B2/S/C3");
+
+        let rule = "B2/S345/C4".parse::<Rule>().unwrap();
+        assert_eq!(rule.states(), 4);
+        assert!(rule.is_generations());
+        assert_eq!(rule.cell_bits(), 2);
+
+        // Two states is an ordinary rule
+        assert_eq!("B3/S23/C2".parse::<Rule>().unwrap(), Rule::LIFE);
+        assert_eq!("23/3/2".parse::<Rule>().unwrap().to_string(), "B3/S23");
+        assert!(!Rule::LIFE.is_generations());
+
+        assert!("B2/S/C1".parse::<Rule>().is_err());
+        assert!("B2/S/C".parse::<Rule>().is_err());
+        assert!("/2/3/4".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn multicolour() {
+        for rule in ["Immigration", "QuadLife"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+
+        let immigration = "immigration".parse::<Rule>().unwrap();
+        let quadlife = "QUADLIFE".parse::<Rule>().unwrap();
+        assert_eq!(immigration.kind, RuleKind::Multicolour { colours: 2 });
+        assert_eq!(quadlife.kind, RuleKind::Multicolour { colours: 4 });
+        assert_eq!((immigration.states(), quadlife.states()), (3, 5));
+        assert_eq!(quadlife.colours().len(), 5);
+        assert!(!quadlife.is_generations());
+        assert_eq!(quadlife.masks(), Rule::LIFE.masks());
+    }
+
+    #[test]
+    fn majority_colour() {
+        let immigration = "Immigration".parse::<Rule>().unwrap();
+        let quadlife = "QuadLife".parse::<Rule>().unwrap();
+        let (red, green, blue, yellow) = (1, 2, 3, 4);
+
+        // Births take the colour of two of the three parents, wherever they are
+        assert_eq!(immigration.next_colour([0, red, 0, green, 0, 0, red, 0, 0]), red);
+        assert_eq!(immigration.next_colour([0, green, green, red, 0, 0, 0, 0, 0]), green);
+        assert_eq!(quadlife.next_colour([0, 0, blue, 0, yellow, 0, 0, 0, blue]), blue);
+        // Three colours between them give the fourth
+        assert_eq!(quadlife.next_colour([0, red, 0, green, 0, blue, 0, 0, 0]), yellow);
+        assert_eq!(quadlife.next_colour([0, yellow, 0, 0, green, 0, 0, red, 0]), blue);
+
+        // Survivors keep their colour whatever their neighbours are, and otherwise it's Life
+        assert_eq!(quadlife.next_colour([green, red, red, 0, 0, 0, 0, 0, 0]), green);
+        assert_eq!(quadlife.next_colour([green, red, blue, yellow, 0, 0, 0, 0, 0]), green);
+        assert_eq!(quadlife.next_colour([green, red, 0, 0, 0, 0, 0, 0, 0]), 0);
+        assert_eq!(immigration.next_colour([0, red, red, 0, 0, 0, 0, 0, 0]), 0);
+        assert_eq!(immigration.next_colour([0, red, red, green, green, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn larger_than_life() {
+        for rule in ["R5,C0,M1,S34..58,B34..45,NM", "R10,C0,M0,S123..212,B123..170,NM", "R3,C4,M1,S4..12,B5..9,NM"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+
+        let rule = "r3,c4,m1,s4..12,b5..9,nm".parse::<Rule>().unwrap();
+        assert_eq!(rule.states(), 4);
+        assert_eq!(rule.kind, RuleKind::LargerThanLife(LargerThanLife { range: 3, states: 4, include_centre: true, birth: [5, 9], survival: [4, 12] }));
+        // Two states can also be written as C2
+        assert_eq!("R5,C2,M1,S34..58,B34..45,NM".parse::<Rule>().unwrap().to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+        // Range one runs as a rule on the 3x3 neighbourhood
+        assert_eq!("R1,C0,M0,S2..3,B3..3,NM".parse::<Rule>().unwrap(), Rule::LIFE);
+        assert_eq!("R1,C3,M0,S2..3,B3..3,NM".parse::<Rule>().unwrap().to_string(), "B3/S23/C3");
+
+        assert!("R5,C0,M1,S58..34,B34..45,NM".parse::<Rule>().is_err());
+        assert!("R5,C0,M1,S34..58,B34..45,NN".parse::<Rule>().is_err());
+        assert!("R17,C0,M1,S34..58,B34..45,NM".parse::<Rule>().is_err());
+        assert!("R5,C0,M1,B34..45,NM".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn neighbourhoods() {
+        for rule in ["B2/S34V", "B2/S34H", "B2/S/C3V", "B24/S35H"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+
+        // Only the north neighbour, the north east or the north west, without the centre
+        let (north, north_east, north_west) = (1 << 7, 1 << 6, 1 << 8);
+        let von_neumann = "b1/sv".parse::<Rule>().unwrap();
+        assert_eq!(von_neumann.neighbourhood(), Neighbourhood::VonNeumann);
+        assert!(von_neumann.next(north));
+        assert!(!von_neumann.next(north_east));
+        assert!(!von_neumann.next(north_west));
+
+        let hexagonal = "B1/SH".parse::<Rule>().unwrap();
+        assert!(hexagonal.next(north));
+        assert!(!hexagonal.next(north_east));
+        assert!(hexagonal.next(north_west));
+
+        assert!("B5/S23V".parse::<Rule>().is_err());
+        assert!("B3/S7H".parse::<Rule>().is_err());
+        assert!("B2a/S23V".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn one_dimensional() {
+        for rule in ["W30", "W110", "W0", "T52,R2", "T6,R1"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+
+        assert_eq!("w30".parse::<Rule>().unwrap().kind, RuleKind::OneDimensional(OneDimensional { range: 1, totalistic: false, code: 30 }));
+        assert_eq!("t52,r2".parse::<Rule>().unwrap().kind, RuleKind::OneDimensional(OneDimensional { range: 2, totalistic: true, code: 52 }));
+
+        assert!("W256".parse::<Rule>().is_err());
+        assert!("T52".parse::<Rule>().is_err());
+        assert!("T16,R1".parse::<Rule>().is_err());
+        assert!("T52,R16".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn margolus() {
+        for rule in ["M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15", "M15,14,13,3,11,5,6,1,7,9,10,2,12,4,8,0", "M15,1,2,3,4,5,6,7,8,9,10,11,12,13,14,0"] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), rule);
+        }
+
+        let rule = "m0, 8, 4, 3, 2, 5, 9, 7, 1, 6, 10, 11, 12, 13, 14, 15".parse::<Rule>().unwrap();
+        assert_eq!(rule.kind, RuleKind::Margolus(Margolus { table: [0, 8, 4, 3, 2, 5, 9, 7, 1, 6, 10, 11, 12, 13, 14, 15] }));
+        assert!(!rule.supports_second_order());
+
+        assert!("M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14".parse::<Rule>().is_err());
+        assert!("M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,16".parse::<Rule>().is_err());
+    }
+}
@@ -0,0 +1,45 @@
+//! Rules written in WGSL by the user, composed into the update when the simulation is built so that
+//! they don't need the crate to be rebuilt
+
+use std::{fs, io, path::Path};
+
+use naga_oil::compose::{ComposableModuleDescriptor, Composer};
+
+/// A WGSL module with `fn next_state(neighbours: u32, alive: bool) -> bool`, which is called with
+/// each cell's count of live Moore neighbours in place of the rule's births and survivals. It can
+/// `#import common` like the built-in shaders
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RuleShader {
+    /// Where the source was loaded from, which errors point at
+    pub path: String,
+    pub source: String,
+}
+
+impl RuleShader {
+    /// What the update shader imports the module as
+    const MODULE_NAME: &str = "rule_shader";
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: path.display().to_string(),
+            source: fs::read_to_string(path)?,
+        })
+    }
+
+    /// The file's name without its extension, which patterns run with it give as their rule
+    pub fn name(&self) -> String {
+        Path::new(&self.path).file_stem().map_or_else(|| self.path.clone(), |stem| stem.to_string_lossy().into_owned())
+    }
+
+    /// Adds the module to `composer` for the update shader to import, failing with naga's error if
+    /// it doesn't parse
+    pub fn add_to(&self, composer: &mut Composer) -> Result<(), String> {
+        let result = composer.add_composable_module(ComposableModuleDescriptor {
+            source: &self.source,
+            file_path: &self.path,
+            as_name: Some(Self::MODULE_NAME.into()),
+            ..Default::default()
+        });
+        result.map(|_| ()).map_err(|error| error.emit_to_string(composer))
+    }
+}
@@ -0,0 +1,638 @@
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use naga_oil::compose::ShaderDefValue;
+
+use crate::world;
+
+/// Most states a rule table can have, as each cell is stored in at most a byte
+pub const MAX_STATES: u32 = 256;
+/// Transitions a table can expand to once variables and symmetries are written out
+const MAX_TRANSITIONS: usize = 1 << 16;
+
+/// States a single input of a transition matches, as a bitset
+type StateSet = [u64; 4];
+
+fn single(state: u32) -> StateSet {
+    let mut set = [0; 4];
+    set[state as usize / 64] |= 1 << (state % 64);
+    set
+}
+
+fn contains(set: &StateSet, state: u32) -> bool {
+    set[state as usize / 64] & (1 << (state % 64)) != 0
+}
+
+fn states_of(set: &StateSet) -> impl Iterator<Item = u32> + '_ {
+    (0..MAX_STATES).filter(|&state| contains(set, state))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TableNeighbourhood {
+    Moore,
+    VonNeumann,
+}
+
+impl TableNeighbourhood {
+    /// Cells a transition looks at, including the centre
+    pub const fn inputs(self) -> usize {
+        match self {
+            TableNeighbourhood::Moore => 9,
+            TableNeighbourhood::VonNeumann => 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Symmetry {
+    None,
+    Rotate2,
+    Rotate4,
+    Rotate8,
+    ReflectHorizontal,
+    Rotate4Reflect,
+    Rotate8Reflect,
+    Permute,
+}
+
+impl Symmetry {
+    fn parse(name: &str, neighbourhood: TableNeighbourhood) -> Option<Self> {
+        let symmetry = match name {
+            "none" => Symmetry::None,
+            "rotate2" => Symmetry::Rotate2,
+            "rotate4" => Symmetry::Rotate4,
+            "rotate8" => Symmetry::Rotate8,
+            "reflect_horizontal" => Symmetry::ReflectHorizontal,
+            "rotate4reflect" => Symmetry::Rotate4Reflect,
+            "rotate8reflect" => Symmetry::Rotate8Reflect,
+            "permute" => Symmetry::Permute,
+            _ => return None,
+        };
+
+        // A quarter turn is a single step around the von Neumann neighbourhood
+        let eighth_turns = matches!(symmetry, Symmetry::Rotate8 | Symmetry::Rotate8Reflect);
+        (!eighth_turns || neighbourhood == TableNeighbourhood::Moore).then_some(symmetry)
+    }
+
+    /// Every arrangement of the neighbours (clockwise from north) which the symmetry treats as
+    /// the same as `ring`
+    fn images(self, ring: &[StateSet]) -> Vec<Vec<StateSet>> {
+        let n = ring.len();
+        let rotate = |steps: usize| (0..n).map(|i| ring[(i + steps) % n]).collect::<Vec<_>>();
+        let reflect = |ring: &[StateSet]| (0..n).map(|i| ring[(n - i) % n]).collect::<Vec<_>>();
+
+        let step = match self {
+            Symmetry::None | Symmetry::ReflectHorizontal => n,
+            Symmetry::Rotate2 => n / 2,
+            Symmetry::Rotate4 | Symmetry::Rotate4Reflect => n / 4,
+            Symmetry::Rotate8 | Symmetry::Rotate8Reflect => 1,
+            Symmetry::Permute => return permutations(ring),
+        };
+
+        let mut images = (0..n).step_by(step).map(rotate).collect::<Vec<_>>();
+        if matches!(self, Symmetry::ReflectHorizontal | Symmetry::Rotate4Reflect | Symmetry::Rotate8Reflect) {
+            images.extend(images.iter().map(|image| reflect(image)).collect::<Vec<_>>());
+        }
+
+        let mut unique = Vec::with_capacity(images.len());
+        for image in images {
+            if !unique.contains(&image) {
+                unique.push(image);
+            }
+        }
+        unique
+    }
+}
+
+/// Every distinct ordering of `ring`, without repeating orderings of equal inputs
+fn permutations(ring: &[StateSet]) -> Vec<Vec<StateSet>> {
+    let mut current = ring.to_vec();
+    current.sort_unstable();
+    let mut all = vec![current.clone()];
+
+    // Standard next lexicographic permutation
+    loop {
+        let Some(i) = (1..current.len()).rev().find(|&i| current[i - 1] < current[i]) else {
+            return all;
+        };
+        let j = (i..current.len()).rev().find(|&j| current[j] > current[i - 1]).unwrap();
+        current.swap(i - 1, j);
+        current[i..].reverse();
+        all.push(current.clone());
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Transition {
+    /// Centre first, then the neighbours clockwise from north
+    inputs: Vec<StateSet>,
+    output: u32,
+}
+
+/// A Golly rule table (`.rule` file with a `@TABLE` section), giving the next state of a cell from
+/// its neighbourhood with the first transition which matches
+#[derive(Clone, Debug)]
+pub struct RuleTable {
+    /// From the `@RULE` line
+    pub name: String,
+    pub states: u32,
+    pub neighbourhood: TableNeighbourhood,
+    transitions: Vec<Transition>,
+    /// sRGB colour of every state
+    pub colours: Vec<[u8; 3]>,
+}
+
+#[derive(Debug)]
+pub enum RuleTableError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for RuleTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleTableError::Io(err) => write!(f, "{err}"),
+            RuleTableError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for RuleTableError {}
+
+impl From<io::Error> for RuleTableError {
+    fn from(err: io::Error) -> Self {
+        RuleTableError::Io(err)
+    }
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> RuleTableError {
+    RuleTableError::Parse { line: line + 1, message: message.into() }
+}
+
+/// One input or the output of a transition as written, before variables are filled in
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Term {
+    State(u32),
+    Variable(String),
+}
+
+impl RuleTable {
+    pub fn load(path: &Path) -> Result<Self, RuleTableError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, RuleTableError> {
+        let mut name = String::new();
+        let mut section = "";
+        let mut has_table = false;
+        let mut states = None;
+        let mut neighbourhood = None;
+        let mut symmetry = None::<(usize, String)>;
+        let mut variables = HashMap::<String, StateSet>::new();
+        let mut rows = Vec::<(usize, Vec<Term>)>::new();
+        let mut colour_lines = Vec::<(usize, Vec<u32>)>::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('@') {
+                let (keyword, rest) = header.split_once(char::is_whitespace).unwrap_or((header, ""));
+                section = keyword;
+                has_table |= keyword == "TABLE";
+                if keyword == "RULE" {
+                    name = rest.trim().to_string();
+                }
+                continue;
+            }
+
+            match section {
+                "TABLE" => {
+                    let parse_state = |value: &str| value
+                        .trim()
+                        .parse::<u32>()
+                        .map_err(|_| parse_error(line_number, format!("'{}' is not a state", value.trim())));
+
+                    if let Some(definition) = line.strip_prefix("var ") {
+                        let (variable, values) = definition
+                            .split_once('=')
+                            .ok_or_else(|| parse_error(line_number, "variables should look like 'var a={0,1}'"))?;
+                        let values = values.trim().trim_start_matches('{').trim_end_matches('}');
+
+                        let mut set = [0; 4];
+                        for value in values.split(',').map(str::trim) {
+                            match variables.get(value) {
+                                Some(other) => (0..4).for_each(|i| set[i] |= other[i]),
+                                None => {
+                                    let state = parse_state(value)?;
+                                    if state >= MAX_STATES {
+                                        return Err(parse_error(line_number, format!("state {state} is out of range")));
+                                    }
+                                    set[state as usize / 64] |= 1 << (state % 64);
+                                },
+                            }
+                        }
+                        variables.insert(variable.trim().to_string(), set);
+                    } else if let Some((key, value)) = line.split_once([':', '=']) {
+                        let value = value.trim();
+                        match key.trim() {
+                            "n_states" | "num_states" => {
+                                let count = parse_state(value)?;
+                                if !(2..=MAX_STATES).contains(&count) {
+                                    return Err(parse_error(line_number, format!("between 2 and {MAX_STATES} states are supported")));
+                                }
+                                states = Some(count);
+                            },
+                            "neighborhood" | "neighbourhood" => neighbourhood = Some(match value {
+                                "Moore" => TableNeighbourhood::Moore,
+                                "vonNeumann" => TableNeighbourhood::VonNeumann,
+                                _ => return Err(parse_error(line_number, format!("unsupported neighbourhood '{value}'"))),
+                            }),
+                            "symmetries" => symmetry = Some((line_number, value.to_string())),
+                            key => return Err(parse_error(line_number, format!("unknown setting '{key}'"))),
+                        }
+                    } else {
+                        let terms = if line.contains(',') {
+                            line.split(',').map(str::trim).map(str::to_string).collect::<Vec<_>>()
+                        } else {
+                            // Tables where every state is a single digit can leave out the commas
+                            line.chars().filter(|c| !c.is_whitespace()).map(String::from).collect()
+                        };
+
+                        let terms = terms
+                            .into_iter()
+                            .map(|term| match term.parse() {
+                                Ok(state) => Term::State(state),
+                                Err(_) => Term::Variable(term),
+                            })
+                            .collect();
+                        rows.push((line_number, terms));
+                    }
+                },
+                "COLORS" => {
+                    let numbers = line
+                        .split(|c: char| c.is_whitespace() || c == ',')
+                        .filter(|value| !value.is_empty())
+                        .map(|value| value.parse::<u32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| parse_error(line_number, "colours should be numbers"))?;
+                    colour_lines.push((line_number, numbers));
+                },
+                _ => {},
+            }
+        }
+
+        if !has_table {
+            return Err(parse_error(0, "no @TABLE section"));
+        }
+        let states = states.ok_or_else(|| parse_error(0, "missing n_states"))?;
+        let neighbourhood = neighbourhood.unwrap_or(TableNeighbourhood::Moore);
+        let symmetry = match symmetry {
+            Some((line_number, name)) => Symmetry::parse(&name, neighbourhood)
+                .ok_or_else(|| parse_error(line_number, format!("unsupported symmetries '{name}' for {neighbourhood:?}")))?,
+            None => Symmetry::None,
+        };
+
+        let mut transitions = Vec::new();
+        for (line_number, terms) in rows {
+            let limit = MAX_TRANSITIONS - transitions.len();
+            transitions.extend(expand(&terms, &variables, states, neighbourhood, symmetry, limit)
+                .map_err(|message| parse_error(line_number, message))?);
+        }
+
+        let mut colours = default_colours(states);
+        for (line_number, numbers) in colour_lines {
+            let mut set = |state: u32, colour: [u32; 3]| match colours.get_mut(state as usize) {
+                Some(entry) => {
+                    *entry = colour.map(|channel| channel.min(255) as u8);
+                    Ok(())
+                },
+                None => Err(parse_error(line_number, format!("state {state} is out of range"))),
+            };
+
+            match numbers[..] {
+                [state, r, g, b] => set(state, [r, g, b])?,
+                // Golly's gradient from state 1 to the last state
+                [r0, g0, b0, r1, g1, b1] => for state in 1..states {
+                    let t = (state - 1) as f32 / (states - 2).max(1) as f32;
+                    let mix = |a: u32, b: u32| (a as f32 + (b as f32 - a as f32) * t).round() as u32;
+                    set(state, [mix(r0, r1), mix(g0, g1), mix(b0, b1)])?;
+                },
+                _ => return Err(parse_error(line_number, "expected 'state r g b' or 'r g b r g b' for a gradient")),
+            }
+        }
+
+        Ok(Self {
+            name,
+            states,
+            neighbourhood,
+            transitions,
+            colours,
+        })
+    }
+
+    /// Bits each cell needs in the world, rounded up so that cells never straddle words
+    pub fn cell_bits(&self) -> u32 {
+        world::cell_bits(self.states)
+    }
+
+    /// Words of transition bits for each input position and state
+    fn chunks(&self) -> usize {
+        self.transitions.len().div_ceil(u32::BITS as usize).max(1)
+    }
+
+    /// The table as the shader reads it. For every input position and state there are
+    /// [`RuleTable::chunks`] words with bit `t` set when transition `t` matches that state there.
+    /// The output of every transition follows
+    pub fn lookup_table(&self) -> Vec<u32> {
+        let chunks = self.chunks();
+        let inputs = self.neighbourhood.inputs();
+        let mut words = vec![0; inputs * self.states as usize * chunks + chunks * u32::BITS as usize];
+
+        for (t, transition) in self.transitions.iter().enumerate() {
+            for (position, set) in transition.inputs.iter().enumerate() {
+                for state in states_of(set).filter(|&state| state < self.states) {
+                    words[(position * self.states as usize + state as usize) * chunks + t / 32] |= 1 << (t % 32);
+                }
+            }
+            words[inputs * self.states as usize * chunks + t] = transition.output;
+        }
+
+        words
+    }
+
+    pub fn shader_defs(&self) -> HashMap<String, ShaderDefValue> {
+        let mut defs = HashMap::from([
+            ("RULE_TABLE".into(), ShaderDefValue::Bool(true)),
+            ("STATES".into(), ShaderDefValue::UInt(self.states)),
+            ("TABLE_INPUTS".into(), ShaderDefValue::UInt(self.neighbourhood.inputs() as u32)),
+            ("TABLE_CHUNKS".into(), ShaderDefValue::UInt(self.chunks() as u32)),
+        ]);
+
+        if self.neighbourhood == TableNeighbourhood::VonNeumann {
+            defs.insert("VON_NEUMANN".into(), ShaderDefValue::Bool(true));
+        }
+
+        defs
+    }
+}
+
+/// Golly's defaults: black for dead cells, then a gradient from red to yellow. Two-state tables
+/// match the rest of the viewer and draw live cells white
+fn default_colours(states: u32) -> Vec<[u8; 3]> {
+    if states == 2 {
+        return vec![[0, 0, 0], [255, 255, 255]];
+    }
+
+    std::iter::once([0, 0, 0])
+        .chain((1..states).map(|state| {
+            let t = (state - 1) as f32 / (states - 2) as f32;
+            [255, (255. * t).round() as u8, 0]
+        }))
+        .collect()
+}
+
+/// Writes out a transition once for every value of each variable used more than once, as those are
+/// bound to the same state wherever they appear, and once for every image of its neighbours under
+/// `symmetry`. Fails without writing them all out when there would be more than `limit`
+fn expand(
+    terms: &[Term],
+    variables: &HashMap<String, StateSet>,
+    states: u32,
+    neighbourhood: TableNeighbourhood,
+    symmetry: Symmetry,
+    limit: usize,
+) -> Result<Vec<Transition>, String> {
+    let inputs = neighbourhood.inputs();
+    if terms.len() != inputs + 1 {
+        return Err(format!("expected {} inputs and an output, found {} values", inputs, terms.len()));
+    }
+
+    let mut bound = Vec::<(&str, &StateSet)>::new();
+    for term in terms {
+        let Term::Variable(name) = term else {
+            continue;
+        };
+        let set = variables.get(name).ok_or_else(|| format!("unknown variable '{name}'"))?;
+
+        let uses = terms.iter().filter(|other| *other == term).count();
+        if uses > 1 && !bound.iter().any(|(bound_name, _)| bound_name == name) {
+            bound.push((name, set));
+        }
+    }
+
+    if let Term::Variable(name) = &terms[inputs] {
+        if !bound.iter().any(|(bound_name, _)| bound_name == name) {
+            return Err(format!("output variable '{name}' isn't an input"));
+        }
+    }
+
+    for term in terms {
+        if let Term::State(state) = term {
+            if *state >= states {
+                return Err(format!("state {state} is out of range"));
+            }
+        }
+    }
+
+    // Every combination of values for the bound variables gives at least one transition, so a row
+    // with too many is turned down before any are written out
+    let values = bound.iter().map(|(_, set)| states_of(set).collect::<Vec<_>>()).collect::<Vec<_>>();
+    let too_many = || format!("table expands to more than {MAX_TRANSITIONS} transitions");
+    let assignments = values
+        .iter()
+        .try_fold(1usize, |count, values| count.checked_mul(values.len()).filter(|&count| count <= limit))
+        .ok_or_else(too_many)?;
+
+    let mut transitions = Vec::new();
+    for index in 0..assignments {
+        // The last bound variable changes fastest, so that transitions keep the order they're written in
+        let mut rest = index;
+        let mut assignment = values
+            .iter()
+            .rev()
+            .map(|values| {
+                let state = values[rest % values.len()];
+                rest /= values.len();
+                state
+            })
+            .collect::<Vec<_>>();
+        assignment.reverse();
+
+        let value = |term: &Term| match term {
+            Term::State(state) => single(*state),
+            Term::Variable(name) => match bound.iter().position(|(bound_name, _)| bound_name == name) {
+                Some(i) => single(assignment[i]),
+                None => variables[name],
+            },
+        };
+
+        let output = match &terms[inputs] {
+            Term::State(state) => *state,
+            Term::Variable(name) => assignment[bound.iter().position(|(bound_name, _)| bound_name == name).unwrap()],
+        };
+
+        let centre = value(&terms[0]);
+        let ring = terms[1..inputs].iter().map(value).collect::<Vec<_>>();
+        for image in symmetry.images(&ring) {
+            let mut inputs = vec![centre];
+            inputs.extend(image);
+            transitions.push(Transition { inputs, output });
+        }
+
+        if transitions.len() > limit {
+            return Err(too_many());
+        }
+    }
+
+    Ok(transitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIREWORLD: &str = "\
+@RULE WireWorld
+
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,1,2,3}
+var i={0,2,3}
+var j={0,2,3}
+var k={0,2,3}
+var l={0,2,3}
+var m={0,2,3}
+var n={0,2,3}
+var o={0,2,3}
+
+1,a,b,c,d,e,f,g,h,2   # head becomes tail
+2,a,b,c,d,e,f,g,h,3   # tail becomes wire
+3,1,i,j,k,l,m,n,o,1   # one head nearby
+3,1,1,i,j,k,l,m,n,1   # two heads nearby
+
+@COLORS
+1 255 255 255
+2 0 128 255
+3 255 128 0
+";
+
+    const LIFE: &str = "\
+@RULE Life
+@TABLE
+n_states:2
+neighborhood:Moore
+symmetries:permute
+var a={0,1}
+var b={0,1}
+var c={0,1}
+var d={0,1}
+var e={0,1}
+var f={0,1}
+var g={0,1}
+var h={0,1}
+0,1,1,1,0,0,0,0,0,1
+1,1,1,0,0,0,0,0,0,1
+1,1,1,1,0,0,0,0,0,1
+1,a,b,c,d,e,f,g,h,0
+";
+
+    /// Next state of the centre from the first matching transition, staying the same when none match
+    fn next(table: &RuleTable, centre: u32, ring: &[u32]) -> u32 {
+        table.transitions
+            .iter()
+            .find(|transition| {
+                contains(&transition.inputs[0], centre)
+                    && transition.inputs[1..].iter().zip(ring).all(|(set, &state)| contains(set, state))
+            })
+            .map_or(centre, |transition| transition.output)
+    }
+
+    #[test]
+    fn wireworld() {
+        let table = RuleTable::parse(WIREWORLD).unwrap();
+        assert_eq!(table.name, "WireWorld");
+        assert_eq!(table.states, 4);
+        assert_eq!(table.neighbourhood, TableNeighbourhood::Moore);
+        assert_eq!(table.cell_bits(), 2);
+
+        assert_eq!(next(&table, 0, &[1; 8]), 0);
+        assert_eq!(next(&table, 1, &[3, 1, 0, 2, 0, 0, 0, 3]), 2);
+        assert_eq!(next(&table, 2, &[0; 8]), 3);
+        // Wire turns into a head next to one or two heads wherever they are, but not three
+        assert_eq!(next(&table, 3, &[0, 0, 0, 0, 0, 1, 0, 3]), 1);
+        assert_eq!(next(&table, 3, &[3, 1, 0, 0, 2, 0, 0, 1]), 1);
+        assert_eq!(next(&table, 3, &[1, 0, 1, 0, 1, 0, 0, 0]), 3);
+        assert_eq!(next(&table, 3, &[3, 2, 0, 0, 0, 0, 3, 0]), 3);
+
+        assert_eq!(table.colours, [[0, 0, 0], [255, 255, 255], [0, 128, 255], [255, 128, 0]]);
+    }
+
+    #[test]
+    fn colour_gradient() {
+        // Golly's gradient runs from state 1 to the last state, and later lines set single states
+        let source = WIREWORLD.replace("1 255 255 255\n", "0 255 0 200 0 255\n");
+        let table = RuleTable::parse(&source).unwrap();
+        assert_eq!(table.colours, [[0, 0, 0], [0, 255, 0], [0, 128, 255], [255, 128, 0]]);
+
+        let source = WIREWORLD.split("@COLORS").next().unwrap().to_string() + "@COLORS\n0 255 0 200 0 255\n";
+        let table = RuleTable::parse(&source).unwrap();
+        assert_eq!(table.colours, [[0, 0, 0], [0, 255, 0], [100, 128, 128], [200, 0, 255]]);
+
+        assert!(RuleTable::parse(&(source + "1 2 3 4 5\n")).is_err());
+    }
+
+    #[test]
+    fn life() {
+        let table = RuleTable::parse(LIFE).unwrap();
+        for neighbours in 0..=u8::MAX {
+            let ring = (0..8).map(|i| (neighbours >> i) as u32 & 1).collect::<Vec<_>>();
+            let count = neighbours.count_ones();
+            assert_eq!(next(&table, 0, &ring), (count == 3) as u32, "{neighbours:08b}");
+            assert_eq!(next(&table, 1, &ring), (count == 2 || count == 3) as u32, "{neighbours:08b}");
+        }
+
+        // Every transition has a bit for its input in each position, followed by its output
+        let chunks = table.chunks();
+        let lookup = table.lookup_table();
+        assert_eq!(lookup.len(), 9 * 2 * chunks + chunks * 32);
+        let outputs = table.transitions.iter().map(|transition| transition.output).collect::<Vec<_>>();
+        assert_eq!(lookup[9 * 2 * chunks..][..outputs.len()], outputs[..]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(RuleTable::parse("@RULE Empty").is_err());
+        assert!(RuleTable::parse("@TABLE\nneighborhood:Moore").is_err());
+        assert!(RuleTable::parse("@TABLE\nn_states:2\nneighborhood:hexagonal").is_err());
+        assert!(RuleTable::parse("@TABLE\nn_states:2\nneighborhood:vonNeumann\nsymmetries:rotate8").is_err());
+        assert!(RuleTable::parse("@TABLE\nn_states:2\n0,0,0,0,0,0,0,0,0,5").is_err());
+    }
+
+    #[test]
+    fn too_many_transitions() {
+        // Four bound variables of 256 states each would be 2^32 transitions
+        let states = (0..256).map(|state| state.to_string()).collect::<Vec<_>>().join(",");
+        let variables = ["a", "b", "c", "d"].map(|name| format!("var {name}={{{states}}}\n")).concat();
+        let source = format!("@TABLE\nn_states:256\n{variables}a,a,b,b,c,c,d,d,0,0\n");
+        assert!(RuleTable::parse(&source).unwrap_err().to_string().contains("more than"));
+
+        // Neighbours permute into 8!, 8!/2! and 8!/3! transitions, which together go past the limit
+        let source = "@TABLE\nn_states:9\nsymmetries:permute\n0,1,2,3,4,5,6,7,8,1\n0,1,1,2,3,4,5,6,7,1\n0,1,1,1,2,3,4,5,6,1\n";
+        assert!(RuleTable::parse(source).is_err());
+    }
+}
@@ -0,0 +1,609 @@
+use std::borrow::Cow;
+
+use glam::{IVec2, UVec2};
+use naga_oil::compose::{ComposableModuleDescriptor, Composer, NagaModuleDescriptor, ShaderDefValue};
+use wgpu::{naga::valid::Capabilities, util::DeviceExt, ShaderStages};
+
+use crate::{adapter_config::AdapterConfig, pattern::Pattern, rendering_utils::SimpleUniformHelper, rule::RuleKind, rule_table::RuleTable, turmite::{Ant, Turmite}, world::World, CellPacking, Settings, WorldBackend, BITS_PER_WORD};
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Default, Debug)]
+struct SimulationUniform {
+    seed: u32,
+    /// Chance of each cell starting alive in a random soup
+    density: f32,
+    birth: u32,
+    survival: u32,
+    /// [`crate::rule::NeighbourhoodTable`] for rules which aren't totalistic
+    neighbourhoods: [[u32; 4]; 4],
+    /// [`crate::Lenia`]'s parameters, in the same order
+    lenia: [f32; 4],
+    /// Chances of a birth, a survival and a cell being updated at all, then padding
+    chances: [f32; 4],
+}
+
+/// Composer with every shared module loaded
+pub fn composer() -> Composer {
+    let mut composer = Composer::default()
+        .with_capabilities(Capabilities::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING);
+
+    composer.add_composable_module(ComposableModuleDescriptor {
+        source: include_str!("wgsl/conway_common.wgsl"),
+        file_path: "wgsl/conway_common.wgsl",
+        ..Default::default()
+    }).unwrap();
+
+    composer.add_composable_module(ComposableModuleDescriptor {
+        source: include_str!("wgsl/world.wgsl"),
+        file_path: "wgsl/world.wgsl",
+        ..Default::default()
+    }).unwrap();
+
+    composer.add_composable_module(ComposableModuleDescriptor {
+        source: include_str!("wgsl/rule_table.wgsl"),
+        file_path: "wgsl/rule_table.wgsl",
+        ..Default::default()
+    }).unwrap();
+
+    composer
+}
+
+/// Agents of a turmite, bound as group 2 of the update along with the turmite's transitions
+struct Ants {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// Where the ants are put back to when the world is reset
+    start: Vec<Ant>,
+}
+
+/// Whatever the update binds as group 2, of which each kind of rule needs at most one
+enum UpdateResources {
+    None,
+    /// Lookup buffer for a rule table
+    RuleTable(wgpu::BindGroup),
+    /// Generations run so far, for one dimensional rules, which write the row below it, and for
+    /// rules with noise, which roll different chances every generation. Lenia's kernel comes after it
+    /// when Lenia cells are updated at random
+    Generation(wgpu::Buffer, wgpu::BindGroup),
+    /// Block offsets of a Margolus rule, one for reading each copy of the world
+    MargolusPhases([wgpu::BindGroup; 2]),
+    /// Lenia's kernel, unless it's bound after the count of generations
+    Kernel(wgpu::BindGroup),
+    Ants(Ants),
+}
+
+/// The world and everything needed to step it, without anything tied to a window
+pub struct Simulation {
+    world: World,
+    compute_pipeline: wgpu::ComputePipeline,
+    init_pipeline: wgpu::ComputePipeline,
+    compute_bind_groups: [wgpu::BindGroup; 2],
+    uniform: SimpleUniformHelper<SimulationUniform>,
+    update_resources: UpdateResources,
+    /// Moves the count of generations on after every update
+    count_generation_pipeline: Option<wgpu::ComputePipeline>,
+    one_dimensional: bool,
+    /// Whether each generation also depends on the one before, so that time can be reversed
+    second_order: bool,
+    population_pipeline: wgpu::ComputePipeline,
+    /// Total filled in by `count_population`, as a little endian `u64`
+    population: wgpu::Buffer,
+    population_bind_group: wgpu::BindGroup,
+    frame_polarity: bool,
+    workgroup_dims: UVec2,
+}
+
+impl Simulation {
+    /// Fails with naga's error when `settings.rule_shader` doesn't compile, which is the only part of
+    /// the update not written here
+    pub fn new(device: &wgpu::Device, limits: &wgpu::Limits, adapter_config: &AdapterConfig, settings: &Settings) -> Result<Self, String> {
+        let mut composer = composer();
+        let world_backend = adapter_config.world_backend;
+
+        let turmite = settings.turmite.as_ref().filter(|_| settings.lenia.is_none());
+        assert!(turmite.is_none() || world_backend == WorldBackend::StorageBuffer, "Ants need a storage buffer world, as textures don't have atomics");
+        let two_state_rule = settings.rule_table.is_none() && settings.lenia.is_none() && turmite.is_none();
+        let second_order = settings.second_order && two_state_rule && settings.rule.supports_second_order();
+        // Both need the copy of the world being written to be read as well
+        let read_output = second_order || turmite.is_some();
+        let one_dimensional = two_state_rule && matches!(settings.rule.kind, RuleKind::OneDimensional(_));
+        let rule_shader = settings.rule_shader.as_ref().filter(|_| two_state_rule && settings.rule.supports_rule_shader());
+
+        // Noise is applied to each cell once it's been updated as usual. Reversing time would need the
+        // same chances rolled backwards, so second order rules are left alone
+        let asynchronous = settings.update_fraction < 1. && !second_order && match (&settings.lenia, turmite, &settings.rule_table) {
+            (Some(_), _, _) => true,
+            (None, None, None) => settings.rule.supports_asynchronous(),
+            _ => false,
+        };
+        let stochastic = (settings.birth_chance < 1. || settings.survival_chance < 1.) && two_state_rule && !second_order && settings.rule.supports_stochastic();
+        let count_generations = one_dimensional || asynchronous || stochastic;
+
+        let cell_bits = turmite.map_or_else(|| settings.rule_table.as_ref().map_or(settings.rule.cell_bits(), RuleTable::cell_bits), Turmite::cell_bits);
+        // Lenia's states are floats, which take a whole word each
+        let (packing, cell_bits) = match settings.lenia {
+            Some(_) => (CellPacking::Float, BITS_PER_WORD),
+            // Storage textures can only be both read and written with one word per texel
+            None if read_output && world_backend == WorldBackend::TextureArray => (CellPacking::Scalar, cell_bits),
+            None => (adapter_config.packing(settings.packing), cell_bits),
+        };
+        let world = World::new(packing, cell_bits, settings.world_size, world_backend, limits, device);
+
+        let mut shader_defs = world.shader_defs();
+        match (&settings.lenia, turmite, &settings.rule_table) {
+            (Some(lenia), _, _) => {
+                shader_defs.extend(lenia.shader_defs());
+            },
+            (None, Some(turmite), _) => {
+                shader_defs.extend(turmite.shader_defs(settings.ant_steps));
+            },
+            (None, None, Some(table)) => {
+                shader_defs.extend(table.shader_defs());
+                shader_defs.insert("MULTISTATE".into(), ShaderDefValue::Bool(true));
+            },
+            (None, None, None) => {
+                if settings.rule.uses_table() {
+                    shader_defs.insert("NEIGHBOURHOOD_TABLE".into(), ShaderDefValue::Bool(true));
+                }
+                match &settings.rule.kind {
+                    RuleKind::LargerThanLife(larger_than_life) => shader_defs.extend(larger_than_life.shader_defs()),
+                    RuleKind::OneDimensional(one_dimensional) => shader_defs.extend(one_dimensional.shader_defs()),
+                    RuleKind::Margolus(margolus) => shader_defs.extend(margolus.shader_defs()),
+                    RuleKind::Multicolour { colours } => shader_defs.extend([
+                        ("LIVE_COLOURS".into(), ShaderDefValue::UInt(*colours)),
+                        ("MULTISTATE".into(), ShaderDefValue::Bool(true)),
+                    ]),
+                    RuleKind::Totalistic { .. } | RuleKind::Table { .. } => {},
+                }
+                if settings.rule.is_generations() {
+                    shader_defs.extend([
+                        ("GENERATIONS".into(), ShaderDefValue::Bool(true)),
+                        ("STATES".into(), ShaderDefValue::UInt(settings.rule.states())),
+                        ("MULTISTATE".into(), ShaderDefValue::Bool(true)),
+                    ]);
+                }
+            },
+        }
+        if read_output {
+            shader_defs.insert("READ_OUTPUT".into(), ShaderDefValue::Bool(true));
+        }
+        if second_order {
+            shader_defs.insert("SECOND_ORDER".into(), ShaderDefValue::Bool(true));
+        }
+        if count_generations {
+            shader_defs.insert("COUNT_GENERATIONS".into(), ShaderDefValue::Bool(true));
+        }
+        if stochastic {
+            shader_defs.insert("STOCHASTIC".into(), ShaderDefValue::Bool(true));
+        }
+        if asynchronous {
+            shader_defs.insert("ASYNCHRONOUS".into(), ShaderDefValue::Bool(true));
+        }
+        if let Some(rule_shader) = rule_shader {
+            rule_shader.add_to(&mut composer)?;
+            shader_defs.insert("RULE_SHADER".into(), ShaderDefValue::Bool(true));
+        }
+        if let Some(tile) = settings.soup_tile {
+            assert!(tile % BITS_PER_WORD == 0, "Soup tiles must be a whole number of words wide");
+            shader_defs.insert("SOUP_TILE".into(), ShaderDefValue::UInt(tile));
+        }
+
+        let compute_module = composer.make_naga_module(NagaModuleDescriptor {
+            source: include_str!("wgsl/conway_compute.wgsl"),
+            file_path: "wgsl/conway_compute.wgsl",
+            shader_defs,
+            ..Default::default()
+        }).map_err(|error| error.emit_to_string(&composer))?;
+
+        let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute"),
+            source: wgpu::ShaderSource::Naga(Cow::Owned(compute_module))
+        });
+
+        let (birth, survival) = settings.rule.masks();
+        let uniform = SimpleUniformHelper::from_inner(SimulationUniform {
+            seed: settings.seed,
+            density: settings.density,
+            birth: birth as u32,
+            survival: survival as u32,
+            neighbourhoods: bytemuck::cast(settings.rule.table()),
+            lenia: settings.lenia.map_or([0.; 4], |lenia| [lenia.radius, lenia.mu, lenia.sigma, lenia.dt]),
+            chances: [settings.birth_chance, settings.survival_chance, settings.update_fraction, 0.],
+        }, device, ShaderStages::COMPUTE);
+
+        let compute_bind_group_layout = world.compute_bind_group_layout(device, read_output);
+
+        let compute_bind_groups = world.compute_bind_groups(&compute_bind_group_layout, device);
+
+        let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&compute_bind_group_layout, uniform.layout()],
+            label: Some("compute_pipeline_layout"),
+            push_constant_ranges: &[]
+        });
+
+        let kernel = settings.lenia.map(|lenia| device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("lenia_kernel"),
+            contents: bytemuck::cast_slice(&lenia.kernel()),
+            usage: wgpu::BufferUsages::STORAGE,
+        }));
+
+        let (update_group_layout, update_resources) = if let Some(turmite) = turmite {
+            let start = Turmite::place_ants(settings.ants, world.size(), settings.seed);
+            // Also drawn straight from the buffer as the instances of the markers
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("ants"),
+                contents: bytemuck::cast_slice(&start),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
+            let transitions = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("turmite"),
+                contents: bytemuck::cast_slice(&turmite.lookup_table()),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+            let layout = storage_bind_group_layout(device, &[false, true]);
+            let bind_group = storage_bind_group(device, &layout, &[&buffer, &transitions]);
+            (Some(layout), UpdateResources::Ants(Ants { buffer, bind_group, start }))
+        } else if let Some(table) = settings.rule_table.as_ref().filter(|_| settings.lenia.is_none()) {
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("rule_table"),
+                contents: bytemuck::cast_slice(&table.lookup_table()),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+            let layout = storage_bind_group_layout(device, &[true]);
+            let bind_group = storage_bind_group(device, &layout, &[&buffer]);
+            (Some(layout), UpdateResources::RuleTable(bind_group))
+        } else if matches!(settings.rule.kind, RuleKind::Margolus(_)) && two_state_rule {
+            // Margolus blocks start one cell further along every other generation, which is picked by
+            // binding the phase matching the copy of the world being read
+            let layout = storage_bind_group_layout(device, &[true]);
+            let bind_groups = [0u32, 1].map(|phase| {
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("margolus_phase"),
+                    contents: bytemuck::bytes_of(&phase),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+                storage_bind_group(device, &layout, &[&buffer])
+            });
+            (Some(layout), UpdateResources::MargolusPhases(bind_groups))
+        } else if count_generations {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("generation"),
+                size: size_of::<u32>() as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let buffers = [&buffer].into_iter().chain(&kernel).collect::<Vec<_>>();
+            let layout = storage_bind_group_layout(device, &[false, true][..buffers.len()]);
+            let bind_group = storage_bind_group(device, &layout, &buffers);
+            (Some(layout), UpdateResources::Generation(buffer, bind_group))
+        } else if let Some(kernel) = &kernel {
+            let layout = storage_bind_group_layout(device, &[true]);
+            let bind_group = storage_bind_group(device, &layout, &[kernel]);
+            (Some(layout), UpdateResources::Kernel(bind_group))
+        } else {
+            (None, UpdateResources::None)
+        };
+
+        let update_pipeline_layout = match &update_group_layout {
+            Some(layout) => &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts: &[&compute_bind_group_layout, uniform.layout(), layout],
+                label: Some("update_pipeline_layout"),
+                push_constant_ranges: &[]
+            }),
+            None => &compute_pipeline_layout,
+        };
+
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            cache: None,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            entry_point: Some("update"),
+            label: Some("update_pipeline"),
+            layout: Some(update_pipeline_layout),
+            module: &compute_shader
+        });
+
+        let count_generation_pipeline = matches!(update_resources, UpdateResources::Generation(..)).then(|| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            cache: None,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            entry_point: Some("count_generation"),
+            label: Some("count_generation_pipeline"),
+            layout: Some(update_pipeline_layout),
+            module: &compute_shader
+        }));
+
+        let init_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            cache: None,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            entry_point: Some("init"),
+            label: Some("init_pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &compute_shader
+        });
+
+        let population = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("population"),
+            size: size_of::<u64>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let population_bind_group_layout = storage_bind_group_layout(device, &[false]);
+        let population_bind_group = storage_bind_group(device, &population_bind_group_layout, &[&population]);
+
+        let population_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&compute_bind_group_layout, uniform.layout(), &population_bind_group_layout],
+            label: Some("population_pipeline_layout"),
+            push_constant_ranges: &[]
+        });
+
+        let population_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            cache: None,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            entry_point: Some("count_population"),
+            label: Some("population_pipeline"),
+            layout: Some(&population_pipeline_layout),
+            module: &compute_shader
+        });
+
+        Ok(Self {
+            workgroup_dims: packing.workgroup_dims(settings.world_size, cell_bits),
+            world,
+            compute_pipeline,
+            init_pipeline,
+            compute_bind_groups,
+            uniform,
+            update_resources,
+            count_generation_pipeline,
+            one_dimensional,
+            second_order,
+            population_pipeline,
+            population,
+            population_bind_group,
+            frame_polarity: false,
+        })
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Size in cells, which may be wider than asked for to fill whole texels
+    pub fn world_size(&self) -> UVec2 {
+        self.world.size()
+    }
+
+    /// Which copy of the world holds the latest generation
+    pub fn current(&self) -> usize {
+        self.frame_polarity as usize
+    }
+
+    /// Places the pattern from the settings, or a random soup when there isn't one
+    pub fn populate(&self, device: &wgpu::Device, queue: &wgpu::Queue, settings: &Settings) {
+        match &settings.pattern {
+            Some(pattern) => {
+                let offset = settings.pattern_offset.unwrap_or_else(|| {
+                    let centred = (settings.world_size.as_ivec2() - pattern.size.as_ivec2()) / 2;
+                    // One dimensional rules start from the top row
+                    if self.one_dimensional { centred.with_y(0) } else { centred }
+                });
+                self.load_pattern(queue, pattern, offset);
+            },
+            None => self.init(device, queue),
+        }
+    }
+
+    /// Writes `pattern` into an empty world with its top left corner at `offset`, clipping anything
+    /// which falls outside
+    pub fn load_pattern(&self, queue: &wgpu::Queue, pattern: &Pattern, offset: IVec2) {
+        let world_size = self.world.size().as_ivec2();
+        let first_row = offset.y.clamp(0, world_size.y);
+        let last_row = (offset.y + pattern.size.y as i32).clamp(0, world_size.y);
+        if first_row == last_row {
+            return;
+        }
+
+        let cell_bits = self.world.cell_bits();
+        let cells_per_word = BITS_PER_WORD / cell_bits;
+        let row_words = self.world.size().x / cells_per_word;
+        let mut words = vec![0u32; ((last_row - first_row) as u32 * row_words) as usize];
+
+        for (i, cell) in pattern.cells.iter().enumerate() {
+            let pos = offset + cell.as_ivec2();
+            if pos.cmplt(IVec2::ZERO).any() || pos.cmpge(world_size).any() {
+                continue;
+            }
+
+            // States the world can't hold are drawn as alive, which is 1 for float cells
+            let state = match pattern.state(i) as u32 {
+                _ if self.world.packing() == CellPacking::Float => 1f32.to_bits(),
+                state if state >> cell_bits == 0 => state,
+                _ => 1,
+            };
+
+            let (x, y) = (pos.x as u32, (pos.y - first_row) as u32);
+            // Most significant bits are the left-most cell
+            words[(y * row_words + x / cells_per_word) as usize] |= state << (BITS_PER_WORD - cell_bits * (x % cells_per_word + 1));
+        }
+
+        self.world.write_rows(queue, self.current(), first_row as u32, &words);
+        self.restart(queue);
+    }
+
+    /// One dimensional rules start again from the top row, noise from its first chances and ants from
+    /// where they were placed
+    fn restart(&self, queue: &wgpu::Queue) {
+        match &self.update_resources {
+            UpdateResources::Generation(buffer, _) => queue.write_buffer(buffer, 0, bytemuck::bytes_of(&0u32)),
+            UpdateResources::Ants(ants) => queue.write_buffer(&ants.buffer, 0, bytemuck::cast_slice(&ants.start)),
+            _ => (),
+        }
+    }
+
+    /// Buffer of every [`Ant`] and how many there are, when running a turmite
+    pub fn ants(&self) -> Option<(&wgpu::Buffer, u32)> {
+        match &self.update_resources {
+            UpdateResources::Ants(ants) => Some((&ants.buffer, ants.start.len() as u32)),
+            _ => None,
+        }
+    }
+
+    pub fn is_second_order(&self) -> bool {
+        self.second_order
+    }
+
+    /// Swaps the current and previous generations of a second order rule, so that stepping runs
+    /// time backwards exactly. The previous generation becomes the one shown
+    pub fn reverse_time(&mut self) {
+        assert!(self.second_order, "Only second order rules can be reversed");
+        self.frame_polarity = !self.frame_polarity;
+    }
+
+    /// Takes effect on the next [`Simulation::init`]
+    pub fn set_seed(&mut self, queue: &wgpu::Queue, seed: u32) {
+        self.uniform.update(queue, |uniform| uniform.seed = seed);
+    }
+
+    /// Fills the world with a random soup generated from the seed
+    pub fn init(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.restart(queue);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Init Encoder")
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Init Pass"),
+                timestamp_writes: None
+            });
+
+            compute_pass.set_pipeline(&self.init_pipeline);
+            // Write into the current copy
+            compute_pass.set_bind_group(0, &self.compute_bind_groups[1 - self.current()], &[]);
+            compute_pass.set_bind_group(1, self.uniform.bind_group(), &[]);
+
+            compute_pass.dispatch_workgroups(self.workgroup_dims.x, self.workgroup_dims.y, 1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Records a single generation
+    pub fn step(&mut self, encoder: &mut wgpu::CommandEncoder, timestamp_writes: Option<wgpu::ComputePassTimestampWrites>) {
+        let turmite = matches!(self.update_resources, UpdateResources::Ants(_));
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Pass"),
+                timestamp_writes
+            });
+
+            compute_pass.set_pipeline(&self.compute_pipeline);
+            // Ants rewrite the latest generation in place, so it's the copy written to
+            let bind_group = if turmite { 1 - self.current() } else { self.current() };
+            compute_pass.set_bind_group(0, &self.compute_bind_groups[bind_group], &[]);
+            compute_pass.set_bind_group(1, self.uniform.bind_group(), &[]);
+            let update_group = match &self.update_resources {
+                UpdateResources::None => None,
+                UpdateResources::RuleTable(bind_group) | UpdateResources::Generation(_, bind_group) | UpdateResources::Kernel(bind_group) => Some(bind_group),
+                UpdateResources::MargolusPhases(bind_groups) => Some(&bind_groups[self.frame_polarity as usize]),
+                UpdateResources::Ants(ants) => Some(&ants.bind_group),
+            };
+            if let Some(update_group) = update_group {
+                compute_pass.set_bind_group(2, update_group, &[]);
+            }
+
+            if let UpdateResources::Ants(ants) = &self.update_resources {
+                // One invocation for each ant
+                compute_pass.dispatch_workgroups((ants.start.len() as u32).div_ceil(Turmite::WORKGROUP_SIZE), 1, 1);
+            } else if self.one_dimensional {
+                // Only one row of the world changes, so a single row of workgroups covers it
+                compute_pass.dispatch_workgroups(self.workgroup_dims.x, 1, 1);
+            } else {
+                compute_pass.dispatch_workgroups(self.workgroup_dims.x, self.workgroup_dims.y, 1);
+            }
+
+            if let Some(count_generation_pipeline) = &self.count_generation_pipeline {
+                compute_pass.set_pipeline(count_generation_pipeline);
+                compute_pass.dispatch_workgroups(1, 1, 1);
+            }
+        }
+
+        if !turmite {
+            self.frame_polarity = !self.frame_polarity;
+        }
+    }
+
+    /// Records counting the live cells in the latest generation, copying the total into
+    /// `destination` as a little endian `u64` at `offset`
+    pub fn count_population(&self, encoder: &mut wgpu::CommandEncoder, destination: &wgpu::Buffer, offset: u64) {
+        encoder.clear_buffer(&self.population, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Population Pass"),
+                timestamp_writes: None
+            });
+
+            compute_pass.set_pipeline(&self.population_pipeline);
+            // Read from the current copy
+            compute_pass.set_bind_group(0, &self.compute_bind_groups[self.current()], &[]);
+            compute_pass.set_bind_group(1, self.uniform.bind_group(), &[]);
+            compute_pass.set_bind_group(2, &self.population_bind_group, &[]);
+
+            compute_pass.dispatch_workgroups(self.workgroup_dims.x, self.workgroup_dims.y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.population, 0, destination, offset, self.population.size());
+    }
+
+    /// Reads the latest generation back, as rows of packed words covering [`World::size`] with
+    /// [`World::cell_bits`] per cell
+    pub fn read_world(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<u32> {
+        let rows = self.world.size().y;
+        self.world.read_rows(device, queue, self.current(), 0..rows)
+    }
+
+    /// Replaces the latest generation with `words`, laid out as [`Simulation::read_world`] gives them
+    pub fn write_world(&self, queue: &wgpu::Queue, words: &[u32]) {
+        self.world.write_rows(queue, self.current(), 0, words);
+    }
+}
+
+/// Layout of a bind group holding only storage buffers, one after another, which is all the extra
+/// groups of the compute shader need
+fn storage_bind_group_layout(device: &wgpu::Device, read_only: &[bool]) -> wgpu::BindGroupLayout {
+    let entries = read_only
+        .iter()
+        .enumerate()
+        .map(|(binding, &read_only)| wgpu::BindGroupLayoutEntry {
+            binding: binding as u32,
+            count: None,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None
+            },
+            visibility: ShaderStages::COMPUTE
+        })
+        .collect::<Vec<_>>();
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &entries,
+    })
+}
+
+fn storage_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffers: &[&wgpu::Buffer]) -> wgpu::BindGroup {
+    let entries = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding()
+        })
+        .collect::<Vec<_>>();
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        label: None,
+        entries: &entries,
+    })
+}
@@ -0,0 +1,113 @@
+use std::{fs::File, io::{self, BufWriter}, path::Path, time::{Duration, Instant}};
+
+use bounded_vec_deque::BoundedVecDeque;
+use serde::Serialize;
+
+use crate::rendering_utils::GpuSpan;
+
+const CPU_THREAD: u32 = 0;
+const GPU_THREAD: u32 = 1;
+
+/// A complete ("X") event in the Chrome tracing format, times are in microseconds
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+/// Rolling record of CPU frame phases and GPU passes which can be dumped as a Chrome tracing /
+/// Perfetto compatible JSON file
+pub struct Trace {
+    origin: Instant,
+    events: BoundedVecDeque<TraceEvent>,
+    /// Added to GPU timestamps to place them on the CPU timeline. The two clocks are lined up once,
+    /// by assuming the first GPU span started when it was submitted, so GPU spans are only
+    /// approximately positioned relative to the CPU
+    gpu_offset_us: Option<f64>,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            origin: Instant::now(),
+            events: BoundedVecDeque::new(capacity),
+            gpu_offset_us: None,
+        }
+    }
+
+    fn since_origin_us(&self, instant: Instant) -> f64 {
+        instant.saturating_duration_since(self.origin).as_secs_f64() * 1e6
+    }
+
+    /// Records a CPU phase which started at `start` and has just finished
+    pub fn cpu_span(&mut self, name: &'static str, start: Instant) {
+        let ts = self.since_origin_us(start);
+        self.events.push_back(TraceEvent {
+            name,
+            cat: "cpu",
+            ph: "X",
+            ts,
+            dur: start.elapsed().as_secs_f64() * 1e6,
+            pid: 0,
+            tid: CPU_THREAD,
+        });
+    }
+
+    /// Runs `f`, recording how long it took
+    pub fn cpu_scope<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.cpu_span(name, start);
+        result
+    }
+
+    pub fn gpu_spans(&mut self, spans: impl IntoIterator<Item = GpuSpan>) {
+        for span in spans {
+            let submitted_us = self.since_origin_us(span.submitted);
+            let offset = *self.gpu_offset_us.get_or_insert(submitted_us - duration_us(span.start));
+
+            self.events.push_back(TraceEvent {
+                name: span.label,
+                cat: "gpu",
+                ph: "X",
+                ts: duration_us(span.start) + offset,
+                dur: duration_us(span.end.saturating_sub(span.start)),
+                pid: 0,
+                tid: GPU_THREAD,
+            });
+        }
+    }
+
+    pub fn write_chrome_trace(&self, path: &Path) -> io::Result<()> {
+        let thread_names = [(CPU_THREAD, "CPU"), (GPU_THREAD, "GPU")]
+            .map(|(tid, name)| serde_json::json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": 0,
+                "tid": tid,
+                "args": { "name": name },
+            }));
+
+        let events = thread_names
+            .into_iter()
+            .chain(self.events.iter().map(|event| serde_json::to_value(event).unwrap()))
+            .collect::<Vec<_>>();
+
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, &serde_json::json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        }))?;
+
+        Ok(())
+    }
+}
+
+fn duration_us(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
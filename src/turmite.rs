@@ -0,0 +1,331 @@
+//! Langton's ant and other turmites, agents which walk over the world reading and rewriting the
+//! cell under them
+
+use std::{collections::HashMap, f32::consts::PI, fmt, str::FromStr};
+
+use glam::{uvec2, UVec2};
+use naga_oil::compose::ShaderDefValue;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{rule_table::MAX_STATES, world};
+
+/// Way an ant turns before moving on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Turn {
+    None,
+    Right,
+    UTurn,
+    Left,
+}
+
+impl Turn {
+    /// Quarter turns clockwise, which is how the shader applies it
+    fn quarter_turns(self) -> u32 {
+        match self {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Turn::None => 'N',
+            Turn::Right => 'R',
+            Turn::UTurn => 'U',
+            Turn::Left => 'L',
+        }
+    }
+
+    /// Golly writes turns as 1, 2, 4 and 8 in turmite tables
+    fn golly_code(self) -> u32 {
+        1 << self.quarter_turns()
+    }
+}
+
+/// What an ant does on a cell of one colour while in one state
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Transition {
+    /// Colour left behind
+    pub write: u32,
+    pub turn: Turn,
+    /// State the ant moves on in
+    pub next: u32,
+}
+
+/// Ants in every state on every colour of cell, such as Langton's ant (`RL`). A string of turns
+/// gives an ant with one state which moves each cell on to the next colour, and Golly's turmite
+/// tables such as `{{{1,2,0},{0,8,0}}}` give anything else
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Turmite {
+    pub colours: u32,
+    pub states: u32,
+    /// Indexed by `state * colours + colour`
+    pub transitions: Vec<Transition>,
+}
+
+/// A single agent, laid out as the shader reads it
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Default, Debug)]
+pub struct Ant {
+    /// Cell the ant is on
+    pub position: UVec2,
+    /// 0 for north, counting clockwise
+    pub direction: u32,
+    pub state: u32,
+}
+
+impl Turmite {
+    /// Steps each ant takes per generation unless asked otherwise, so that a frame covers many steps
+    /// rather than just one. llvmpipe stops loops after 65535 iterations, so this stays under it
+    pub const DEFAULT_STEPS_PER_GENERATION: u32 = 1 << 15;
+    /// Each ant gets an invocation of its own, so this only keeps the buffer of ants reasonable
+    pub const MAX_ANTS: u32 = 1 << 16;
+    /// Ants moved by each workgroup of the update
+    pub const WORKGROUP_SIZE: u32 = 64;
+    /// Ants only cover a small area quickly, so there's no need for a huge world
+    pub const DEFAULT_WORLD_SIZE: UVec2 = uvec2(4096, 4096);
+
+    /// Parses a string of turns such as `LLRR`, one for each colour
+    fn parse_turns(turns: &str) -> Result<Self, String> {
+        let transitions = turns
+            .chars()
+            .map(|c| match c.to_ascii_uppercase() {
+                'N' => Ok(Turn::None),
+                'R' => Ok(Turn::Right),
+                'U' => Ok(Turn::UTurn),
+                'L' => Ok(Turn::Left),
+                _ => Err(format!("'{c}' is not a turn, which are L, R, N or U")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let colours = transitions.len() as u32;
+        let transitions = transitions
+            .into_iter()
+            .enumerate()
+            .map(|(colour, turn)| Transition { write: (colour as u32 + 1) % colours, turn, next: 0 })
+            .collect();
+        Ok(Self { colours, states: 1, transitions })
+    }
+
+    /// Parses a Golly turmite table, a list for every state of `{write, turn, next state}` for
+    /// every colour
+    fn parse_table(table: &str) -> Result<Self, String> {
+        let mut states: Vec<Vec<Vec<u32>>> = Vec::new();
+        let mut depth = 0;
+        let mut number = String::new();
+
+        for c in table.chars().filter(|c| !c.is_whitespace()) {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            if !number.is_empty() {
+                let transition = states.last_mut().and_then(|colours| colours.last_mut()).filter(|_| depth == 3);
+                transition.ok_or("Numbers in a turmite table belong in the innermost braces")?.push(number.parse().map_err(|_| format!("'{number}' is too large"))?);
+                number.clear();
+            }
+
+            match c {
+                '{' => {
+                    depth += 1;
+                    match depth {
+                        2 => states.push(Vec::new()),
+                        3 => states.last_mut().unwrap().push(Vec::new()),
+                        4.. => return Err("Turmite tables only nest three braces deep".into()),
+                        _ => {},
+                    }
+                },
+                '}' if depth > 0 => depth -= 1,
+                ',' => {},
+                _ => return Err(format!("Unexpected '{c}' in a turmite table")),
+            }
+        }
+        if depth != 0 {
+            return Err("Unbalanced braces in a turmite table".into());
+        }
+
+        let colours = states.first().map_or(0, Vec::len) as u32;
+        let state_count = states.len() as u32;
+        if states.iter().any(|state| state.len() as u32 != colours) {
+            return Err("Every state of a turmite table needs a transition for each colour".into());
+        }
+
+        let transitions = states
+            .into_iter()
+            .flatten()
+            .map(|transition| {
+                let [write, turn, next] = transition[..] else {
+                    return Err(format!("Turmite transitions are {{write, turn, next state}}, not {transition:?}"));
+                };
+                let turn = match turn {
+                    1 => Turn::None,
+                    2 => Turn::Right,
+                    4 => Turn::UTurn,
+                    8 => Turn::Left,
+                    _ => return Err(format!("{turn} is not a turn, which are 1, 2, 4 or 8")),
+                };
+                if write >= colours || next >= state_count {
+                    return Err(format!("Transition {{{write}, {}, {next}}} is outside the table", turn.golly_code()));
+                }
+                Ok(Transition { write, turn, next })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { colours, states: state_count, transitions })
+    }
+
+    pub fn cell_bits(&self) -> u32 {
+        world::cell_bits(self.colours)
+    }
+
+    /// Whether this is an ant which only moves cells on to the next colour, so can be written as turns
+    fn is_ant(&self) -> bool {
+        self.states == 1 && self.transitions.iter().enumerate().all(|(colour, transition)| transition.write == (colour as u32 + 1) % self.colours)
+    }
+
+    /// Every ant takes `steps` steps each generation
+    pub fn shader_defs(&self, steps: u32) -> HashMap<String, ShaderDefValue> {
+        HashMap::from([
+            ("TURMITES".into(), ShaderDefValue::Bool(true)),
+            ("COLOURS".into(), ShaderDefValue::UInt(self.colours)),
+            ("ANT_STEPS".into(), ShaderDefValue::UInt(steps)),
+            ("ANT_WORKGROUP_SIZE".into(), ShaderDefValue::UInt(Self::WORKGROUP_SIZE)),
+        ])
+    }
+
+    /// Transitions as the shader reads them, with the colour written in the lowest byte, then the
+    /// quarter turns clockwise and then the next state
+    pub fn lookup_table(&self) -> Vec<u32> {
+        self.transitions
+            .iter()
+            .map(|transition| transition.write | (transition.turn.quarter_turns() << 8) | (transition.next << 16))
+            .collect()
+    }
+
+    /// The first ant in the middle of the world facing north, and the rest scattered around it
+    /// from `seed`
+    pub fn place_ants(count: u32, world_size: UVec2, seed: u32) -> Vec<Ant> {
+        const SCATTER: u32 = 256;
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        let centre = world_size / 2;
+        let spread = world_size.min(UVec2::splat(SCATTER));
+
+        (0..count)
+            .map(|i| match i {
+                0 => Ant { position: centre, ..Default::default() },
+                _ => Ant {
+                    position: centre - spread / 2 + uvec2(rng.gen_range(0..spread.x), rng.gen_range(0..spread.y)),
+                    direction: rng.gen_range(0..4),
+                    state: 0,
+                },
+            })
+            .collect()
+    }
+
+    /// sRGB colour of every cell colour. Colour 0 is black, and for ants with more than two colours
+    /// the rest go around the hues
+    pub fn colours(&self) -> Vec<[u8; 3]> {
+        let hue = |colour: u32| {
+            let angle = (colour - 1) as f32 / (self.colours - 1) as f32 * 2. * PI;
+            [0., 2., 4.].map(|offset: f32| ((0.5 + 0.5 * (angle - offset * PI / 3.).cos()) * 255.).round() as u8)
+        };
+
+        (0..self.colours)
+            .map(|colour| match colour {
+                0 => [0, 0, 0],
+                _ if self.colours == 2 => [255, 255, 255],
+                _ => hue(colour),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Turmite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let turmite = if s.starts_with('{') { Self::parse_table(s)? } else { Self::parse_turns(s)? };
+
+        if turmite.colours < 2 {
+            return Err("Turmites need at least two colours".into());
+        }
+        if turmite.colours > MAX_STATES {
+            return Err(format!("Turmites can have at most {MAX_STATES} colours, not {}", turmite.colours));
+        }
+        if turmite.states > u16::MAX as u32 + 1 {
+            return Err(format!("Turmites can have at most {} states, not {}", u16::MAX as u32 + 1, turmite.states));
+        }
+        Ok(turmite)
+    }
+}
+
+impl fmt::Display for Turmite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ant() {
+            return write!(f, "{}", self.transitions.iter().map(|transition| transition.turn.letter()).collect::<String>());
+        }
+
+        let states = self.transitions
+            .chunks(self.colours as usize)
+            .map(|state| {
+                let colours = state
+                    .iter()
+                    .map(|transition| format!("{{{},{},{}}}", transition.write, transition.turn.golly_code(), transition.next))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", colours.join(","))
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{{{}}}", states.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns() {
+        for turns in ["RL", "LLRR", "RLR", "LRRRRRLLR"] {
+            assert_eq!(turns.parse::<Turmite>().unwrap().to_string(), turns);
+        }
+
+        let ant = "llrr".parse::<Turmite>().unwrap();
+        assert_eq!((ant.colours, ant.states), (4, 1));
+        assert_eq!(ant.transitions[3], Transition { write: 0, turn: Turn::Right, next: 0 });
+    }
+
+    #[test]
+    fn table() {
+        // Langton's ant as a table is still an ant, so it's written as turns
+        let langton = "{{{1,2,0},{0,8,0}}}".parse::<Turmite>().unwrap();
+        assert_eq!(langton, "RL".parse().unwrap());
+        assert_eq!(langton.to_string(), "RL");
+        assert_eq!(langton.lookup_table(), [1 | (1 << 8), 3 << 8]);
+
+        // Fibonacci spiral, with two states
+        let spiral = "{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}";
+        let turmite = spiral.parse::<Turmite>().unwrap();
+        assert_eq!((turmite.colours, turmite.states), (2, 2));
+        assert_eq!(turmite.to_string(), spiral);
+        assert_eq!("{ {{1, 8, 1}, {1, 8, 1}}, {{1, 2, 1}, {0, 1, 0}} }".parse::<Turmite>().unwrap(), turmite);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("RX".parse::<Turmite>().is_err());
+        assert!("R".parse::<Turmite>().is_err());
+        // Unbalanced braces
+        assert!("{{{1,2,0},{0,8,0}}".parse::<Turmite>().is_err());
+        assert!("{{{1,2,0},{0,8,0}}}}".parse::<Turmite>().is_err());
+        // 3 isn't a turn
+        assert!("{{{1,3,0},{0,8,0}}}".parse::<Turmite>().is_err());
+        // Writing colour 2 of two, or moving on to state 1 of one
+        assert!("{{{2,2,0},{0,8,0}}}".parse::<Turmite>().is_err());
+        assert!("{{{1,2,1},{0,8,0}}}".parse::<Turmite>().is_err());
+        // Missing a transition
+        assert!("{{{1,2,0},{0,8}}}".parse::<Turmite>().is_err());
+        assert!("{{{1,8,1},{1,8,1}},{{1,2,1}}}".parse::<Turmite>().is_err());
+    }
+}
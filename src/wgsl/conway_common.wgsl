@@ -1,3 +1,21 @@
 #define_import_path common
 
-const BITS_PER_PIXEL: u32 = 32u;
\ No newline at end of file
+const BITS_PER_WORD: u32 = 32u;
+const WORDS_PER_PIXEL: u32 = #WORDS_PER_PIXEL;
+const BITS_PER_PIXEL: u32 = #BITS_PER_PIXEL;
+// Cells can take more than one bit when they have more than two states
+const BITS_PER_CELL: u32 = #BITS_PER_CELL;
+const CELLS_PER_WORD: u32 = BITS_PER_WORD / BITS_PER_CELL;
+const CELLS_PER_PIXEL: u32 = BITS_PER_PIXEL / BITS_PER_CELL;
+// Shifted down rather than up, as float cells take a whole word
+const CELL_MASK: u32 = 0xffffffffu >> (BITS_PER_WORD - BITS_PER_CELL);
+
+// How far a cell's state is shifted up within its word, the left-most cell being the most significant
+fn cell_shift(index: u32) -> u32 {
+    return BITS_PER_WORD - BITS_PER_CELL * (index % CELLS_PER_WORD + 1u);
+}
+
+// State of cell `index` of a pixel, counting from the left
+fn cell_state(pixel: vec4u, index: u32) -> u32 {
+    return (pixel[index / CELLS_PER_WORD] >> cell_shift(index)) & CELL_MASK;
+}
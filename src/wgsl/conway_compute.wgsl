@@ -1,49 +1,240 @@
 #import common
+#import world
+#ifdef RULE_TABLE
+#import rule_table
+#endif
+#ifdef RULE_SHADER
+#import rule_shader
+#endif
 
+#ifdef STORAGE_BUFFER_WORLD
+#ifdef TURMITES
+// Ants write single cells of words which other ants can be writing at the same time
+@group(0) @binding(4) var<storage, read_write> output_0: array<atomic<u32>>;
+#if CHUNK_COUNT > 1
+@group(0) @binding(5) var<storage, read_write> output_1: array<atomic<u32>>;
+#endif
+#if CHUNK_COUNT > 2
+@group(0) @binding(6) var<storage, read_write> output_2: array<atomic<u32>>;
+#endif
+#if CHUNK_COUNT > 3
+@group(0) @binding(7) var<storage, read_write> output_3: array<atomic<u32>>;
+#endif
+#else
+@group(0) @binding(4) var<storage, read_write> output_0: array<u32>;
+#if CHUNK_COUNT > 1
+@group(0) @binding(5) var<storage, read_write> output_1: array<u32>;
+#endif
+#if CHUNK_COUNT > 2
+@group(0) @binding(6) var<storage, read_write> output_2: array<u32>;
+#endif
+#if CHUNK_COUNT > 3
+@group(0) @binding(7) var<storage, read_write> output_3: array<u32>;
+#endif
+#endif
+#else
+#ifdef VECTOR_PACKING
+@group(0) @binding(0) var input: binding_array< texture_storage_2d<rgba32uint, read> >;
+@group(0) @binding(1) var output: binding_array< texture_storage_2d<rgba32uint, write> >;
+#else
+#ifdef FLOAT_CELLS
+@group(0) @binding(0) var input: binding_array< texture_storage_2d<r32float, read> >;
+@group(0) @binding(1) var output: binding_array< texture_storage_2d<r32float, write> >;
+#else
 @group(0) @binding(0) var input: binding_array< texture_storage_2d<r32uint, read> >;
+#ifdef READ_OUTPUT
+@group(0) @binding(1) var output: binding_array< texture_storage_2d<r32uint, read_write> >;
+#else
 @group(0) @binding(1) var output: binding_array< texture_storage_2d<r32uint, write> >;
+#endif
+#endif
+#endif
+#endif
+
+struct Simulation {
+    seed: u32,
+    density: f32,
+    // Bit n is set when n neighbours cause a birth / survival
+    birth: u32,
+    survival: u32,
+    // For rules which aren't totalistic, bit n is set when the 3x3 neighbourhood n causes a birth or
+    // survival. Neighbourhoods are read from the north west, which is the most significant bit
+    neighbourhoods: array<vec4u, 4>,
+    // Lenia's kernel radius, which the kernel is worked out from on the CPU, growth centre and
+    // width, and time step
+    radius: f32,
+    mu: f32,
+    sigma: f32,
+    dt: f32,
+    // Chances of a birth or survival happening, and of a cell being updated at all
+    birth_chance: f32,
+    survival_chance: f32,
+    update_chance: f32,
+}
+
+@group(1) @binding(0) var<uniform> simulation: Simulation;
+
+#ifdef COUNT_GENERATIONS
+// Generations run so far
+@group(2) @binding(0) var<storage, read_write> generation: u32;
+#endif
 
 // Most significant -> LEFT
 // Least significant -> RIGHT
+// Within a pixel, `.x` is the left-most word
 
-fn get_pixel(pos: vec2i) -> u32 {
-    if (pos.x < 0 || pos.y < 0) {
-        return 0u;
+fn get_pixel(pos: vec2i) -> vec4u {
+    if !world::in_bounds(pos) {
+        return vec4u(0u);
+    }
+
+#ifdef STORAGE_BUFFER_WORLD
+    return world::load_linear(world::linear_index(pos));
+#else
+    let tile_pos = vec2u(pos / #TILE_SIZE);
+    let texture_pos = vec2u(pos % #TILE_SIZE);
+
+#ifdef FLOAT_CELLS
+    // Float cells are passed around as their bits so that the rest of the shader only sees words
+    return bitcast<vec4u>(textureLoad(input[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos));
+#else
+    return textureLoad(input[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos);
+#endif
+#endif
+}
+
+#ifdef STORAGE_BUFFER_WORLD
+#ifdef TURMITES
+// Clears the bits of `mask` in a word being written and then sets `bits`, leaving the rest of the
+// word to whatever other invocations write. Returns the word from before
+fn write_bits(chunk: u32, offset: u32, mask: u32, bits: u32) -> u32 {
+    var old = 0u;
+    switch chunk {
+        case 0u: { old = atomicAnd(&output_0[offset], ~mask); atomicOr(&output_0[offset], bits); }
+#if CHUNK_COUNT > 1
+        case 1u: { old = atomicAnd(&output_1[offset], ~mask); atomicOr(&output_1[offset], bits); }
+#endif
+#if CHUNK_COUNT > 2
+        case 2u: { old = atomicAnd(&output_2[offset], ~mask); atomicOr(&output_2[offset], bits); }
+#endif
+#if CHUNK_COUNT > 3
+        case 3u: { old = atomicAnd(&output_3[offset], ~mask); atomicOr(&output_3[offset], bits); }
+#endif
+        default: {}
     }
+    return old;
+}
+
+fn store_word(chunk: u32, offset: u32, word: u32) {
+    write_bits(chunk, offset, 0xffffffffu, word);
+}
+#else
+fn store_word(chunk: u32, offset: u32, word: u32) {
+    switch chunk {
+        case 0u: { output_0[offset] = word; }
+#if CHUNK_COUNT > 1
+        case 1u: { output_1[offset] = word; }
+#endif
+#if CHUNK_COUNT > 2
+        case 2u: { output_2[offset] = word; }
+#endif
+#if CHUNK_COUNT > 3
+        case 3u: { output_3[offset] = word; }
+#endif
+        default: {}
+    }
+}
+#endif
+#endif
+
+#ifdef READ_OUTPUT
+#ifdef STORAGE_BUFFER_WORLD
+#ifdef TURMITES
+fn load_output_word(chunk: u32, offset: u32) -> u32 {
+    // Nothing is cleared or set, so this only reads the word
+    return write_bits(chunk, offset, 0u, 0u);
+}
+#else
+fn load_output_word(chunk: u32, offset: u32) -> u32 {
+    switch chunk {
+        case 0u: { return output_0[offset]; }
+#if CHUNK_COUNT > 1
+        case 1u: { return output_1[offset]; }
+#endif
+#if CHUNK_COUNT > 2
+        case 2u: { return output_2[offset]; }
+#endif
+#if CHUNK_COUNT > 3
+        case 3u: { return output_3[offset]; }
+#endif
+        default: { return 0u; }
+    }
+}
+#endif
+#endif
 
+// Reads back the copy being written, which still holds whatever was there before until it's overwritten
+fn get_output(pos: vec2i) -> vec4u {
+    if !world::in_bounds(pos) {
+        return vec4u(0u);
+    }
+
+#ifdef STORAGE_BUFFER_WORLD
+    let index = world::linear_index(pos);
+    let chunk = index / #CHUNK_PIXELS;
+    let offset = (index % #CHUNK_PIXELS) * common::WORDS_PER_PIXEL;
+    var pixel = vec4u(0u);
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        pixel[w] = load_output_word(chunk, offset + w);
+    }
+    return pixel;
+#else
     let tile_pos = vec2u(pos / #TILE_SIZE);
     let texture_pos = vec2u(pos % #TILE_SIZE);
 
-    return textureLoad(input[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos).r;
+    return textureLoad(output[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos);
+#endif
 }
+#endif
 
-fn set_pixel(pos: vec2i, value: u32) {
-    if (pos.x < 0 || pos.y < 0) {
+fn set_pixel(pos: vec2i, value: vec4u) {
+    if !world::in_bounds(pos) {
         return;
     }
 
+#ifdef STORAGE_BUFFER_WORLD
+    let index = world::linear_index(pos);
+    let chunk = index / #CHUNK_PIXELS;
+    let offset = (index % #CHUNK_PIXELS) * common::WORDS_PER_PIXEL;
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        store_word(chunk, offset + w, value[w]);
+    }
+#else
     let tile_pos = vec2u(pos / #TILE_SIZE);
     let texture_pos = vec2u(pos % #TILE_SIZE);
 
-    textureStore(output[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos, vec4u(value, 0, 0, 0));
+#ifdef FLOAT_CELLS
+    textureStore(output[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos, bitcast<vec4f>(value));
+#else
+    textureStore(output[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos, value);
+#endif
+#endif
 }
 
-fn count_right(current: u32, above: u32, below: u32, pixel_loc: vec2i) -> u32 {
-    return ((get_pixel(pixel_loc + vec2i(1, -1)) >> 31u) & 1u) + // NE
-        ((get_pixel(pixel_loc + vec2i(1, 1)) >> 31u) & 1u) + // SE
-        ((get_pixel(pixel_loc + vec2i(1, 0)) >> 31u) & 1u) + // E
-        countOneBits(extractBits(above, 0u, 2u)) +
-        countOneBits(extractBits(below, 0u, 2u)) +
-        ((current >> 1u) & 1u);
+// Words are passed as columns of (above, current, below)
+
+fn count_right(middle: vec3u, right: vec3u) -> u32 {
+    return dot(right >> vec3u(31u), vec3u(1u)) + // NE, E, SE
+        countOneBits(extractBits(middle.x, 0u, 2u)) +
+        countOneBits(extractBits(middle.z, 0u, 2u)) +
+        ((middle.y >> 1u) & 1u);
 }
 
-fn count_left(current: u32, above: u32, below: u32, pixel_loc: vec2i) -> u32 {
-    return (get_pixel(pixel_loc + vec2i(-1, -1)) & 1u) + // NW
-        (get_pixel(pixel_loc + vec2i(-1, 1)) & 1u) + // SW
-        (get_pixel(pixel_loc + vec2i(-1, 0)) & 1u) + // W
-        countOneBits(extractBits(above, 30u, 2u)) +
-        countOneBits(extractBits(below, 30u, 2u)) +
-        ((current >> 30u) & 1u);
+fn count_left(middle: vec3u, left: vec3u) -> u32 {
+    return dot(left & vec3u(1u), vec3u(1u)) + // NW, W, SW
+        countOneBits(extractBits(middle.x, 30u, 2u)) +
+        countOneBits(extractBits(middle.z, 30u, 2u)) +
+        ((middle.y >> 30u) & 1u);
 }
 
 fn count_middle(current: u32, above: u32, below: u32, pos: u32) -> u32 {
@@ -54,14 +245,102 @@ fn count_middle(current: u32, above: u32, below: u32, pos: u32) -> u32 {
 }
 
 fn map(neighbors: u32, alive: bool) -> bool {
-    if (alive) {
-        // Survival: live cell needs 2 or 3 neighbors to survive
-        return neighbors == 2u || neighbors == 3u;
-    } else {
-        // Birth: dead cell needs exactly 3 neighbors to become alive
-        return neighbors == 3u;
+#ifdef RULE_SHADER
+    return rule_shader::next_state(neighbors, alive);
+#else
+    let mask = select(simulation.birth, simulation.survival, alive);
+    return ((mask >> neighbors) & 1u) == 1u;
+#endif
+}
+
+#ifdef NEIGHBOURHOOD_TABLE
+fn lookup(neighbourhood: u32) -> bool {
+    let word = simulation.neighbourhoods[neighbourhood / 128u][(neighbourhood / 32u) % 4u];
+    return ((word >> (neighbourhood % 32u)) & 1u) == 1u;
+}
+
+// Three cells of a row centred on bit `i` of `middle`, the left-most being the most significant
+fn row_cells(left: u32, middle: u32, right: u32, i: u32) -> u32 {
+    if i == 0u {
+        return ((middle & 3u) << 1u) | (right >> 31u);
+    }
+    if i == common::BITS_PER_WORD - 1u {
+        return ((left & 1u) << 2u) | (middle >> 30u);
+    }
+    return extractBits(middle, i - 1u, 3u);
+}
+#endif
+
+#ifdef GENERATIONS
+// Only cells in state 1 count as live neighbours. Live cells which don't survive start dying, moving
+// on a state every generation until they wrap around to dead
+fn next_generation(neighbourhood: array<u32, 9>) -> u32 {
+#ifdef NEIGHBOURHOOD_TABLE
+    // Bit of the neighbourhood each neighbour sets, in Golly's order
+    let bits = array<u32, 8>(7u, 6u, 3u, 0u, 1u, 2u, 5u, 8u);
+    var index = 0u;
+    for (var i = 0u; i < 8u; i++) {
+        index |= u32(neighbourhood[i + 1u] == 1u) << bits[i];
+    }
+    let born = lookup(index);
+    let survives = lookup(index | (1u << 4u));
+#else
+    var neighbors = 0u;
+    for (var i = 1u; i < 9u; i++) {
+        neighbors += u32(neighbourhood[i] == 1u);
+    }
+    let born = map(neighbors, false);
+    let survives = map(neighbors, true);
+#endif
+
+    let state = neighbourhood[0];
+    if state == 0u {
+        return u32(born);
+    }
+    if state == 1u && survives {
+        return 1u;
+    }
+    return (state + 1u) % #STATES;
+}
+#endif
+
+#ifdef LIVE_COLOURS
+const LIVE_COLOURS: u32 = #LIVE_COLOURS;
+
+// Every state after dead is a live cell of a different colour. Newborn cells take the colour most of
+// their parents have, or with three parents of different colours the one none of them have
+fn next_colour(neighbourhood: array<u32, 9>) -> u32 {
+    var neighbors = 0u;
+    var parents = vec4u(0u);
+    for (var i = 1u; i < 9u; i++) {
+        let colour = neighbourhood[i];
+        if colour != 0u {
+            neighbors += 1u;
+            parents[colour - 1u] += 1u;
+        }
+    }
+
+    let state = neighbourhood[0];
+    if state != 0u {
+        return select(0u, state, map(neighbors, true));
+    }
+    if !map(neighbors, false) {
+        return 0u;
+    }
+
+    for (var colour = 0u; colour < LIVE_COLOURS; colour++) {
+        if parents[colour] * 2u > neighbors {
+            return colour + 1u;
+        }
     }
+    for (var colour = 0u; colour < LIVE_COLOURS; colour++) {
+        if parents[colour] == 0u {
+            return colour + 1u;
+        }
+    }
+    return 1u;
 }
+#endif
 
 // https://marc-b-reynolds.github.io/math/2016/03/29/weyl_hash.html
 const W0 = 0x3504f333u;   // 3*2309*128413
@@ -79,36 +358,644 @@ fn hash(pos: vec2u) -> u32 {
     return x;
 }
 
+// Final mix from MurmurHash3, so that neighbouring seeds give unrelated soups
+fn fmix32(value: u32) -> u32 {
+    var h = value;
+    h ^= h >> 16u;
+    h *= 0x85ebca6bu;
+    h ^= h >> 13u;
+    h *= 0xc2b2ae35u;
+    h ^= h >> 16u;
+    return h;
+}
+
+fn seeded_hash(pos: vec2u) -> u32 {
+    return fmix32(hash(pos) ^ fmix32(simulation.seed));
+}
+
+#ifdef COUNT_GENERATIONS
+// Mask with every bit of each cell of a pixel set when a roll for that cell comes up under `chance`.
+// Rolls differ for every seed, generation and `salt`
+fn chance_mask(location: vec2i, salt: u32, chance: f32) -> vec4u {
+    let generation_hash = fmix32(generation * 4u + salt);
+    var mask = vec4u(0u);
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        let word_x = (u32(location.x) * common::WORDS_PER_PIXEL + w) * common::CELLS_PER_WORD;
+        for (var i = 0u; i < common::CELLS_PER_WORD; i++) {
+            let roll = f32(fmix32(seeded_hash(vec2u(word_x + i, u32(location.y))) ^ generation_hash) >> 8u) / 16777216.0;
+            if roll < chance {
+                mask[w] |= common::CELL_MASK << common::cell_shift(i);
+            }
+        }
+    }
+    return mask;
+}
+#endif
+
+// Word `w` of the middle pixel of a row of pixels, with the words either side of it
+fn row_words(row: array<vec4u, 3>, w: u32) -> vec3u {
+    var words = vec3u(row[0][common::WORDS_PER_PIXEL - 1u], row[1][w], row[2].x);
+    if w > 0u {
+        words.x = row[1][w - 1u];
+    }
+    if w < common::WORDS_PER_PIXEL - 1u {
+        words.z = row[1][w + 1u];
+    }
+    return words;
+}
+
+#ifdef SOUP_TILE
+const SOUP_SIZE: u32 = 16u;
+
+// Soups only fill a square in the middle of each tile
+fn in_soup(cell: vec2u) -> bool {
+    let local = cell % #SOUP_TILE;
+    let start = (#SOUP_TILE - SOUP_SIZE) / 2u;
+    return all(local >= vec2u(start)) && all(local < vec2u(start + SOUP_SIZE));
+}
+#endif
+
 @compute @workgroup_size(8, 8, 1)
 fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
-    // set_pixel(vec2i(invocation_id.xy), 1u);
-    let lit = (hash(vec2u(invocation_id.x << 16u, invocation_id.y << 16u)) & 1u) == 0u;
+    var data = vec4u(0u);
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        let word_x = (invocation_id.x * common::WORDS_PER_PIXEL + w) * common::CELLS_PER_WORD;
+        for (var i = 0u; i < common::CELLS_PER_WORD; i++) {
+            let cell = vec2u(word_x + i, invocation_id.y);
+            // 24 bits is all an f32 can hold exactly
+            let chance = f32(seeded_hash(cell) >> 8u) / 16777216.0;
+            var alive = chance < simulation.density;
+#ifdef ONE_DIMENSIONAL
+            // The first row is the first generation, the rest fill up as it runs
+            alive = alive && cell.y == 0u;
+#endif
+#ifdef SOUP_TILE
+            alive = alive && in_soup(cell);
+#endif
+#ifdef TURMITES
+            // Ants start on an empty world
+            alive = false;
+#endif
+#ifdef RULE_TABLE
+            // Live cells take any of the other states
+            let state = select(0u, 1u + fmix32(seeded_hash(cell)) % (#STATES - 1u), alive);
+#else
+#ifdef LIVE_COLOURS
+            // Live cells take any of the colours
+            let state = select(0u, 1u + fmix32(seeded_hash(cell)) % LIVE_COLOURS, alive);
+#else
+#ifdef LENIA
+            // Live cells take any state between 0 and 1
+            let state = select(0u, bitcast<u32>(f32(fmix32(seeded_hash(cell)) >> 8u) / 16777216.0), alive);
+#else
+            let state = u32(alive);
+#endif
+#endif
+#endif
+            data[w] |= state << common::cell_shift(i);
+        }
+    }
+    set_pixel(vec2i(invocation_id.xy), data);
+}
 
-    if lit {
-        let data = hash(invocation_id.xy);
-        set_pixel(vec2i(invocation_id.xy), data);
+#ifdef NEIGHBOURHOOD_TABLE
+// Every cell looks up its whole neighbourhood, as rules which aren't totalistic can't just count
+fn update_word(left: vec3u, middle: vec3u, right: vec3u) -> u32 {
+    var out = 0u;
+    for (var i = 0u; i < common::BITS_PER_WORD; i++) {
+        let neighbourhood = (row_cells(left.x, middle.x, right.x, i) << 6u) |
+            (row_cells(left.y, middle.y, right.y, i) << 3u) |
+            row_cells(left.z, middle.z, right.z, i);
+        out |= u32(lookup(neighbourhood)) << i;
     }
+    return out;
 }
+#else
+fn update_word(left: vec3u, middle: vec3u, right: vec3u) -> u32 {
+    let current = middle.y;
+    var out = 0u;
 
-@compute @workgroup_size(8, 8, 1)
-fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
-    let location = vec2i(invocation_id.xy);
+    // left to right
+    for (var i = 1u; i < common::BITS_PER_WORD - 1; i++) {
+        let neighbors = count_middle(current, middle.x, middle.z, i);
+        let alive = ((current >> i) & 1u) == 1u;
+        out |= u32(map(neighbors, alive)) << i;
+    }
+
+    out |= u32(map(count_left(middle, left), ((current >> 31u) & 1u) == 1u)) << 31u;
+    out |= u32(map(count_right(middle, right), (current & 1u) == 1u));
+
+    return out;
+}
+#endif
+
+#ifdef MARGOLUS
+// Eight blocks of four bits each
+const MARGOLUS_LOW: u32 = #MARGOLUS_LOW;
+const MARGOLUS_HIGH: u32 = #MARGOLUS_HIGH;
+
+// 0 when blocks start on even rows and columns, 1 when they start one cell further along
+@group(2) @binding(0) var<storage, read> phase: u32;
+
+// Bit `bit` of the middle of a row of words, where -1 and 32 reach into the words either side
+fn bit_at(words: vec3u, bit: i32) -> u32 {
+    if bit < 0 {
+        return words.z >> 31u;
+    }
+    if bit >= i32(common::BITS_PER_WORD) {
+        return words.x & 1u;
+    }
+    return (words.y >> u32(bit)) & 1u;
+}
+
+// Every cell works out the 2x2 block it's in, then picks itself out of what the block becomes.
+// Blocks have a bit for each cell, 1 for the top left, 2 for the top right, 4 for the bottom left
+// and 8 for the bottom right
+fn update_pixel(location: vec2i) -> vec4u {
+    let bottom = u32(location.y + i32(phase)) & 1u;
+    let other_y = location.y + select(1, -1, bottom == 1u);
+    let rows = array<array<vec4u, 3>, 2>(
+        array<vec4u, 3>(get_pixel(location + vec2i(-1, 0)), get_pixel(location), get_pixel(location + vec2i(1, 0))),
+        array<vec4u, 3>(get_pixel(vec2i(location.x - 1, other_y)), get_pixel(vec2i(location.x, other_y)), get_pixel(vec2i(location.x + 1, other_y))),
+    );
 
+    var out = vec4u(0u);
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        let own = row_words(rows[0], w);
+        let other = row_words(rows[1], w);
+        for (var bit = 0u; bit < common::BITS_PER_WORD; bit++) {
+            // Words hold a whole number of blocks, so the cell's place within its word is enough
+            let right = (common::BITS_PER_WORD - 1u - bit + phase) & 1u;
+            // Cells further left are in higher bits
+            let other_bit = i32(bit) + select(-1, 1, right == 1u);
+            let position = right | (bottom << 1u);
+
+            let block = (bit_at(own, i32(bit)) << position) |
+                (bit_at(own, other_bit) << (position ^ 1u)) |
+                (bit_at(other, i32(bit)) << (position ^ 2u)) |
+                (bit_at(other, other_bit) << (position ^ 3u));
+            let next = (select(MARGOLUS_LOW, MARGOLUS_HIGH, block >= 8u) >> ((block % 8u) * 4u)) & 15u;
+            out[w] |= ((next >> position) & 1u) << bit;
+        }
+    }
+
+    return out;
+}
+#else
+#ifdef LENIA
+struct KernelTap {
+    offset: vec2i,
+    weight: f32,
+}
+
+// Every cell within the kernel, with weights adding up to 1. It comes after the count of generations
+// when there is one
+#ifdef COUNT_GENERATIONS
+@group(2) @binding(1) var<storage, read> kernel: array<KernelTap>;
+#else
+@group(2) @binding(0) var<storage, read> kernel: array<KernelTap>;
+#endif
+
+fn cell_value(pos: vec2i) -> f32 {
+    return bitcast<f32>(get_pixel(pos).x);
+}
+
+// Between -1 and 1, highest when the average around a cell is `mu`
+fn growth(potential: f32) -> f32 {
+    let offset = potential - simulation.mu;
+    return 2. * exp(-offset * offset / (2. * simulation.sigma * simulation.sigma)) - 1.;
+}
+
+// Each pixel is a single cell, which moves towards its growth by the weighted average of the cells
+// within the kernel. Cells outside the world count as 0, but still carry weight
+fn update_pixel(location: vec2i) -> vec4u {
+    var potential = 0.;
+    for (var i = 0u; i < arrayLength(&kernel); i++) {
+        potential += kernel[i].weight * cell_value(location + kernel[i].offset);
+    }
+
+    let next = clamp(cell_value(location) + simulation.dt * growth(potential), 0., 1.);
+    return vec4u(bitcast<u32>(next), 0u, 0u, 0u);
+}
+#else
+#ifdef LARGER_THAN_LIFE
+const RANGE: u32 = #RANGE;
+const ROWS: u32 = 2u * RANGE + 1u;
+const BIRTH_MIN: u32 = #BIRTH_MIN;
+const BIRTH_MAX: u32 = #BIRTH_MAX;
+const SURVIVAL_MIN: u32 = #SURVIVAL_MIN;
+const SURVIVAL_MAX: u32 = #SURVIVAL_MAX;
+
+fn in_interval(count: u32, min: u32, max: u32) -> bool {
+    return count >= min && count <= max;
+}
+#endif
+
+#ifdef MULTISTATE
+// State of the cell `x` cells from the left of the middle pixel of `row`, which holds the pixels to
+// the left, middle and right
+fn row_state(row: array<vec4u, 3>, x: i32) -> u32 {
+    if x < 0 {
+        return common::cell_state(row[0], common::CELLS_PER_PIXEL - 1u);
+    }
+    if x >= i32(common::CELLS_PER_PIXEL) {
+        return common::cell_state(row[2], 0u);
+    }
+    return common::cell_state(row[1], u32(x));
+}
+
+#ifdef LARGER_THAN_LIFE
+// Pixels either side of the middle one which the range reaches into
+const REACH: u32 = (RANGE + common::CELLS_PER_PIXEL - 1u) / common::CELLS_PER_PIXEL;
+const SPAN: u32 = 2u * REACH + 1u;
+
+// State of the cell `x` cells from the left of the middle pixel of `row`, which holds `REACH`
+// pixels either side of it
+fn reach_state(row: array<vec4u, SPAN>, x: i32) -> u32 {
+    let cell = u32(x + i32(REACH * common::CELLS_PER_PIXEL));
+    return common::cell_state(row[cell / common::CELLS_PER_PIXEL], cell % common::CELLS_PER_PIXEL);
+}
+
+// Larger than Life rules with more than two states count the cells in state 1 within range of each
+// cell along every row, sliding the window from one cell to the next, then add up the rows. They
+// otherwise work like Generations rules
+fn update_pixel(location: vec2i) -> vec4u {
+    var counts = array<u32, common::CELLS_PER_PIXEL>();
+    var middle = vec4u(0u);
+    for (var dy = 0u; dy < ROWS; dy++) {
+        var row: array<vec4u, SPAN>;
+        for (var dx = 0u; dx < SPAN; dx++) {
+            row[dx] = get_pixel(location + vec2i(i32(dx) - i32(REACH), i32(dy) - i32(RANGE)));
+        }
+        if dy == RANGE {
+            middle = row[REACH];
+        }
+
+        var count = 0u;
+        for (var dx = -i32(RANGE); dx <= i32(RANGE); dx++) {
+            count += u32(reach_state(row, dx) == 1u);
+        }
+        counts[0] += count;
+        for (var i = 1; i < i32(common::CELLS_PER_PIXEL); i++) {
+            count += u32(reach_state(row, i + i32(RANGE)) == 1u);
+            count -= u32(reach_state(row, i - i32(RANGE) - 1) == 1u);
+            counts[i] += count;
+        }
+    }
+
+    var out = vec4u(0u);
+    for (var i = 0u; i < common::CELLS_PER_PIXEL; i++) {
+        var count = counts[i];
+        let state = common::cell_state(middle, i);
+#ifndef INCLUDE_CENTRE
+        count -= u32(state == 1u);
+#endif
+        var next = (state + 1u) % #STATES;
+        if state == 0u {
+            next = u32(in_interval(count, BIRTH_MIN, BIRTH_MAX));
+        } else if state == 1u && in_interval(count, SURVIVAL_MIN, SURVIVAL_MAX) {
+            next = 1u;
+        }
+        out[i / common::CELLS_PER_WORD] |= next << common::cell_shift(i);
+    }
+
+    return out;
+}
+#else
+// Cells with more than two states are updated one at a time from the states of their neighbours
+fn update_pixel(location: vec2i) -> vec4u {
+    var rows: array<array<vec4u, 3>, 3>;
+    for (var dy = 0; dy < 3; dy++) {
+        for (var dx = 0; dx < 3; dx++) {
+            rows[dy][dx] = get_pixel(location + vec2i(dx - 1, dy - 1));
+        }
+    }
+
+    var out = vec4u(0u);
+    for (var i = 0u; i < common::CELLS_PER_PIXEL; i++) {
+        let x = i32(i);
+        // Golly's order: the centre, then clockwise from north
+        let neighbourhood = array<u32, 9>(
+            row_state(rows[1], x),
+            row_state(rows[0], x),
+            row_state(rows[0], x + 1),
+            row_state(rows[1], x + 1),
+            row_state(rows[2], x + 1),
+            row_state(rows[2], x),
+            row_state(rows[2], x - 1),
+            row_state(rows[1], x - 1),
+            row_state(rows[0], x - 1),
+        );
+
+#ifdef RULE_TABLE
+        let state = rule_table::next_state(neighbourhood);
+#else
+#ifdef LIVE_COLOURS
+        let state = next_colour(neighbourhood);
+#else
+        let state = next_generation(neighbourhood);
+#endif
+#endif
+        out[i / common::CELLS_PER_WORD] |= state << common::cell_shift(i);
+    }
+
+    return out;
+}
+#endif
+#else
+#ifdef LARGER_THAN_LIFE
+// Live cells among bits `low` to `high` of a word, ignoring any outside it
+fn count_bits(word: u32, low: i32, high: i32) -> u32 {
+    let first = max(low, 0);
+    let last = min(high, i32(common::BITS_PER_WORD) - 1);
+    if first > last {
+        return 0u;
+    }
+    return countOneBits(extractBits(word, u32(first), u32(last - first + 1)));
+}
+
+// Live cells within range of bit `bit` of the middle of a row of words. The range is never more
+// than a word, so it can't reach past the words either side
+fn count_row(words: vec3u, bit: i32) -> u32 {
+    let range = i32(RANGE);
+    let word_bits = i32(common::BITS_PER_WORD);
+    return count_bits(words.x, bit - range - word_bits, bit + range - word_bits) +
+        count_bits(words.y, bit - range, bit + range) +
+        count_bits(words.z, bit - range + word_bits, bit + range + word_bits);
+}
+
+// Larger than Life counts the square around each cell one row at a time, adding up the rows after
+fn update_pixel(location: vec2i) -> vec4u {
+    var rows: array<array<vec4u, 3>, ROWS>;
+    for (var dy = 0u; dy < ROWS; dy++) {
+        let y = location.y + i32(dy) - i32(RANGE);
+        for (var dx = 0; dx < 3; dx++) {
+            rows[dy][dx] = get_pixel(vec2i(location.x + dx - 1, y));
+        }
+    }
+
+    var out = vec4u(0u);
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        var counts = array<u32, common::BITS_PER_WORD>();
+        for (var dy = 0u; dy < ROWS; dy++) {
+            let words = row_words(rows[dy], w);
+            for (var bit = 0u; bit < common::BITS_PER_WORD; bit++) {
+                counts[bit] += count_row(words, i32(bit));
+            }
+        }
+
+        let current = rows[RANGE][1][w];
+        for (var bit = 0u; bit < common::BITS_PER_WORD; bit++) {
+            let alive = ((current >> bit) & 1u) == 1u;
+            var count = counts[bit];
+#ifndef INCLUDE_CENTRE
+            count -= u32(alive);
+#endif
+            let next = select(
+                in_interval(count, BIRTH_MIN, BIRTH_MAX),
+                in_interval(count, SURVIVAL_MIN, SURVIVAL_MAX),
+                alive,
+            );
+            out[w] |= u32(next) << bit;
+        }
+    }
+
+    return out;
+}
+#else
+fn update_pixel(location: vec2i) -> vec4u {
     let above = get_pixel(location + vec2i(0, -1));
     let below = get_pixel(location + vec2i(0, 1));
     let current = get_pixel(location);
 
-    var out = 0u;
+    // Only the words touching this pixel are needed from either side
+    let left = vec3u(
+        get_pixel(location + vec2i(-1, -1))[common::WORDS_PER_PIXEL - 1u],
+        get_pixel(location + vec2i(-1, 0))[common::WORDS_PER_PIXEL - 1u],
+        get_pixel(location + vec2i(-1, 1))[common::WORDS_PER_PIXEL - 1u],
+    );
+    let right = vec3u(
+        get_pixel(location + vec2i(1, -1)).x,
+        get_pixel(location + vec2i(1, 0)).x,
+        get_pixel(location + vec2i(1, 1)).x,
+    );
 
-    // left to right
-    for (var i = 1u; i < common::BITS_PER_PIXEL - 1; i++) {
-        let neighbors = count_middle(current, above, below, i);
-        let alive = ((current >> i) & 1u) == 1u;
-        out |= u32(map(neighbors, alive)) << i;
+    var out = vec4u(0u);
+
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        let middle = vec3u(above[w], current[w], below[w]);
+        var left_word = left;
+        var right_word = right;
+        if w > 0u {
+            left_word = vec3u(above[w - 1u], current[w - 1u], below[w - 1u]);
+        }
+        if w < common::WORDS_PER_PIXEL - 1u {
+            right_word = vec3u(above[w + 1u], current[w + 1u], below[w + 1u]);
+        }
+        out[w] = update_word(left_word, middle, right_word);
     }
 
-    out |= u32(map(count_left(current, above, below, location), ((current >> 31u) & 1u) == 1u)) << 31u;
-    out |= u32(map(count_right(current, above, below, location), (current & 1u) == 1u));
+    return out;
+}
+#endif
+#endif
+#endif
+#endif
+
+#ifdef ONE_DIMENSIONAL
+const RULE_CODE: u32 = #RULE_CODE;
+const ROW_RANGE: u32 = #ROW_RANGE;
+
+// Cells within range of bit `bit` of the middle of a row of words, the left-most being the most
+// significant. The window is never more than a word wide, so it spans at most two words
+fn window(words: vec3u, bit: u32) -> u32 {
+    let parts = array<u32, 3>(words.z, words.y, words.x);
+    let low = common::BITS_PER_WORD + bit - ROW_RANGE;
+    let part = low / common::BITS_PER_WORD;
+    let shift = low % common::BITS_PER_WORD;
+
+    var bits = parts[part] >> shift;
+    if shift > 0u {
+        bits |= parts[part + 1u] << (common::BITS_PER_WORD - shift);
+    }
+    return extractBits(bits, 0u, 2u * ROW_RANGE + 1u);
+}
+
+fn next_cell(words: vec3u, bit: u32) -> u32 {
+#ifdef TOTALISTIC
+    let index = countOneBits(window(words, bit));
+#else
+    let index = window(words, bit);
+#endif
+    return (RULE_CODE >> index) & 1u;
+}
+
+// Only the first two rows of invocations do anything, as the latest generation is the row `generation`.
+// One copies the latest generation across, so that both copies of the world keep the whole history,
+// and the other writes the next generation on the row below
+@compute @workgroup_size(8, 8, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let x = i32(invocation_id.x);
+    let y = i32(generation);
+    if invocation_id.y == 0u {
+        set_pixel(vec2i(x, y), get_pixel(vec2i(x, y)));
+    }
+    if invocation_id.y != 1u {
+        return;
+    }
+
+    let row = array<vec4u, 3>(get_pixel(vec2i(x - 1, y)), get_pixel(vec2i(x, y)), get_pixel(vec2i(x + 1, y)));
+    var out = vec4u(0u);
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        let words = row_words(row, w);
+        for (var bit = 0u; bit < common::BITS_PER_WORD; bit++) {
+            out[w] |= next_cell(words, bit) << bit;
+        }
+    }
+    set_pixel(vec2i(x, y + 1), out);
+}
+#else
+#ifdef TURMITES
+const COLOURS: u32 = #COLOURS;
+const ANT_STEPS: u32 = #ANT_STEPS;
+
+struct Ant {
+    position: vec2u,
+    // 0 for north, counting clockwise
+    direction: u32,
+    state: u32,
+}
+
+@group(2) @binding(0) var<storage, read_write> ants: array<Ant>;
+// For every state and colour, the colour written in the lowest byte, then the quarter turns
+// clockwise and then the next state
+@group(2) @binding(1) var<storage, read> turmite: array<u32>;
+
+// Chunk and offset of the word of the world holding `cell`, then the cell's shift within the word
+fn cell_word(cell: vec2u) -> vec3u {
+    let index = world::linear_index(vec2i(i32(cell.x / common::CELLS_PER_PIXEL), i32(cell.y)));
+    let cell_index = cell.x % common::CELLS_PER_PIXEL;
+    let offset = (index % #CHUNK_PIXELS) * common::WORDS_PER_PIXEL + cell_index / common::CELLS_PER_WORD;
+    return vec3u(index / #CHUNK_PIXELS, offset, common::cell_shift(cell_index));
+}
+
+// Ants rewrite the latest generation in place, reading it back from the copy being written. Cells
+// loaded with states past the turmite's colours wrap around, rather than reading past its table
+fn cell_colour(cell: vec2u) -> u32 {
+    let word = cell_word(cell);
+    return ((load_output_word(word.x, word.y) >> word.z) & common::CELL_MASK) % COLOURS;
+}
+
+// Only touches the cell's own bits, so ants writing other cells of the same word at once don't
+// undo each other
+fn set_cell_colour(cell: vec2u, colour: u32) {
+    let word = cell_word(cell);
+    write_bits(word.x, word.y, common::CELL_MASK << word.z, colour << word.z);
+}
+
+// Each invocation moves one ant. Nothing keeps ants in step with each other, so where ants meet
+// which of them gets to a cell first can differ between runs. Ants leaving one side of the world
+// come back on the other
+@compute @workgroup_size(#ANT_WORKGROUP_SIZE, 1, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let i = invocation_id.x;
+    if i >= arrayLength(&ants) {
+        return;
+    }
+    let size = vec2u(#PIXELS_X * common::CELLS_PER_PIXEL, #PIXELS_Y);
+    let moves = array<vec2u, 4>(vec2u(0u, size.y - 1u), vec2u(1u, 0u), vec2u(0u, 1u), vec2u(size.x - 1u, 0u));
+
+    var ant = ants[i];
+    for (var step = 0u; step < ANT_STEPS; step++) {
+        let transition = turmite[ant.state * COLOURS + cell_colour(ant.position)];
+
+        set_cell_colour(ant.position, transition & 0xffu);
+        ant.direction = (ant.direction + ((transition >> 8u) & 0xffu)) % 4u;
+        ant.state = transition >> 16u;
+        ant.position = (ant.position + moves[ant.direction]) % size;
+    }
+    ants[i] = ant;
+}
+#else
+@compute @workgroup_size(8, 8, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let location = vec2i(invocation_id.xy);
+    var out = update_pixel(location);
+#ifdef STOCHASTIC
+    // Births and survivals only happen by chance, otherwise the cell stays dead or dies
+    let current = get_pixel(location);
+    out &= (~current & chance_mask(location, 0u, simulation.birth_chance)) | (current & chance_mask(location, 1u, simulation.survival_chance));
+#endif
+#ifdef ASYNCHRONOUS
+    // Cells which aren't updated this generation stay as they were
+    let updated = chance_mask(location, 2u, simulation.update_chance);
+    out = (out & updated) | (get_pixel(location) & ~updated);
+#endif
+#ifdef SECOND_ORDER
+    // The copy being written holds the generation before the current one. Undoing this only needs
+    // the same step with the two generations swapped, as x ^ p ^ p = x
+    out ^= get_output(location);
+#endif
+
+#ifdef SOUP_TILE
+    // The first row and column of every tile are kept dead so that neighbouring soups can't interact.
+    // Tiles are a multiple of the word size, so only the left-most cell of a word can be on the border
+    if invocation_id.y % #SOUP_TILE == 0u {
+        out = vec4u(0u);
+    }
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        if ((invocation_id.x * common::WORDS_PER_PIXEL + w) * common::CELLS_PER_WORD) % #SOUP_TILE == 0u {
+            out[w] &= ~(common::CELL_MASK << common::cell_shift(0u));
+        }
+    }
+#endif
 
     set_pixel(location, out);
 }
+#endif
+#endif
+
+#ifdef COUNT_GENERATIONS
+// Run after every update, once the new generation is written
+@compute @workgroup_size(1, 1, 1)
+fn count_generation() {
+    generation += 1u;
+}
+#endif
+
+// 64 bit total as (low, high) words, as there can be more than 2^32 cells
+@group(2) @binding(0) var<storage, read_write> population: array<atomic<u32>, 2>;
+
+var<workgroup> workgroup_population: atomic<u32>;
+
+@compute @workgroup_size(8, 8, 1)
+fn count_population(
+    @builtin(global_invocation_id) invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let pixel = get_pixel(vec2i(invocation_id.xy));
+
+    // Folds every cell's bits into its lowest, which is set for any live state
+    let lowest_bits = 0xffffffffu / common::CELL_MASK;
+
+    var count = 0u;
+    for (var w = 0u; w < common::WORDS_PER_PIXEL; w++) {
+        var word = pixel[w];
+        for (var bits = 1u; bits < common::BITS_PER_CELL; bits *= 2u) {
+            word |= word >> bits;
+        }
+        count += countOneBits(word & lowest_bits);
+    }
+    atomicAdd(&workgroup_population, count);
+
+    workgroupBarrier();
+
+    if local_index == 0u {
+        let total = atomicLoad(&workgroup_population);
+        let low = atomicAdd(&population[0], total);
+        // Carry into the high word on overflow
+        if low + total < low {
+            atomicAdd(&population[1], 1u);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 #import common
+#import world
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
@@ -11,18 +12,43 @@ struct CameraUniform {
     zoom: f32
 }
 
+// Linear colour of every state
+struct Palette {
+    colours: array<vec4<f32>, 256>,
+}
+
 
-@group(0) @binding(0) var world: binding_array< texture_storage_2d<r32uint, read> >;
+#ifndef STORAGE_BUFFER_WORLD
+#ifdef VECTOR_PACKING
+@group(0) @binding(0) var world_tiles: binding_array< texture_storage_2d<rgba32uint, read> >;
+#else
+#ifdef FLOAT_CELLS
+@group(0) @binding(0) var world_tiles: binding_array< texture_storage_2d<r32float, read> >;
+#else
+@group(0) @binding(0) var world_tiles: binding_array< texture_storage_2d<r32uint, read> >;
+#endif
+#endif
+#endif
 @group(1) @binding(0) var<uniform> camera: CameraUniform;
+@group(2) @binding(0) var<uniform> palette: Palette;
+
+fn get_pixel(pos: vec2i) -> vec4u {
+    if !world::in_bounds(pos) {
+        return vec4u(0u);
+    }
 
-fn get_pixel(pos: vec2i) -> u32 {
+#ifdef STORAGE_BUFFER_WORLD
+    return world::load_linear(world::linear_index(pos));
+#else
     let tile_pos = vec2u(pos / #TILE_SIZE);
     let texture_pos = vec2u(pos % #TILE_SIZE);
-    let tile_index = (tile_pos.y * #GRID_WIDTH) + tile_pos.x;
-    if tile_index >= #GRID_LENGTH {
-        return 0u;
-    }
-    return textureLoad(world[tile_index], texture_pos).r;
+
+#ifdef FLOAT_CELLS
+    return bitcast<vec4u>(textureLoad(world_tiles[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos));
+#else
+    return textureLoad(world_tiles[tile_pos.y * #GRID_WIDTH + tile_pos.x], texture_pos);
+#endif
+#endif
 }
 
 @vertex
@@ -45,19 +71,29 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 
 @fragment
 fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let world_pos = (vertex.tex_coords * camera.screen_resolution - camera.screen_resolution / 2.) * camera.zoom + camera.centre;
+    var world_pos = (vertex.tex_coords * camera.screen_resolution - camera.screen_resolution / 2.) * camera.zoom + camera.centre;
+#ifdef HEXAGONAL
+    // Each row is drawn half a cell left of the one above, so that the six neighbours of a
+    // hexagonal rule surround each cell
+    world_pos.x += floor(world_pos.y) / 2.;
+#endif
     let pos = vec2i(world_pos);
     if pos.x < 0 || pos.y < 0 {
         return vec4f(0.);
     }
     // return vec4<f32>(fract(world_pos.x / 100.0), 0.0, 0.0, 1.0);
-    let pixel_pos = vec2i(pos.x / i32(common::BITS_PER_PIXEL), pos.y);
+    let pixel_pos = vec2i(pos.x / i32(common::CELLS_PER_PIXEL), pos.y);
     let pixel = get_pixel(pixel_pos);
     // let pixel = get_pixel(vec2i(0));
 
-    let colour = (pixel >> (common::BITS_PER_PIXEL - 1 - (u32(pos.x) % common::BITS_PER_PIXEL))) & 1u;
+#ifdef FLOAT_CELLS
+    // The palette is a colour map, spread evenly from 0 to 1
+    let state = u32(round(clamp(bitcast<f32>(pixel.x), 0., 1.) * 255.));
+#else
+    let state = common::cell_state(pixel, u32(pos.x) % common::CELLS_PER_PIXEL);
+#endif
 
     // let boundary = pos.x % i32(common::BITS_PER_PIXEL) == 0;
     // return vec4<f32>(f32(colour), vec2f(pos % #TILE_SIZE) / vec2f(#TILE_SIZE), 0);
-    return vec4<f32>(vec3f(colour), 0);
+    return vec4<f32>(palette.colours[state].rgb, 0);
 }
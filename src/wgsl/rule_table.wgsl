@@ -0,0 +1,34 @@
+#define_import_path rule_table
+
+// For every input position and state, `TABLE_CHUNKS` words with a bit set for each transition
+// matching that state there, followed by the output of every transition. Shares group 2 with the
+// population total, which only `count_population` uses
+@group(2) @binding(0) var<storage, read> table: array<u32>;
+
+const OUTPUTS: u32 = #TABLE_INPUTS * #STATES * #TABLE_CHUNKS;
+
+// `neighbourhood` is in Golly's order: the centre, then clockwise from north
+fn next_state(neighbourhood: array<u32, 9>) -> u32 {
+    var cells = neighbourhood;
+
+    for (var chunk = 0u; chunk < #TABLE_CHUNKS; chunk++) {
+        var matching = 0xffffffffu;
+        for (var input = 0u; input < #TABLE_INPUTS; input++) {
+#ifdef VON_NEUMANN
+            // Only the centre and the orthogonal neighbours
+            let position = max(2u * input, 1u) - 1u;
+#else
+            let position = input;
+#endif
+            matching &= table[(input * #STATES + cells[position]) * #TABLE_CHUNKS + chunk];
+        }
+
+        // The first matching transition wins
+        if matching != 0u {
+            return table[OUTPUTS + chunk * 32u + firstTrailingBit(matching)];
+        }
+    }
+
+    // Cells no transition matches stay as they are
+    return cells[0];
+}
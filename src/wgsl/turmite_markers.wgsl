@@ -0,0 +1,53 @@
+// Draws every ant of a turmite as a triangle pointing the way it's facing, over the world
+
+struct CameraUniform {
+    screen_resolution: vec2<f32>,
+    centre: vec2<f32>,
+    zoom: f32
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) colour: vec3<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+// Markers never get smaller than this many screen pixels, so that ants can still be found when
+// zoomed out
+const MIN_SIZE: f32 = 12.0;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) ant: u32,
+    @location(0) cell: vec2<u32>,
+    @location(1) direction: u32,
+) -> VertexOutput {
+    // Pointing north, in cells from the middle of the ant's cell
+    let corners = array<vec2<f32>, 3>(vec2(0.0, -0.5), vec2(-0.4, 0.5), vec2(0.4, 0.5));
+    var corner = corners[vertex_index] * max(1.0, MIN_SIZE * camera.zoom);
+    // A quarter turn clockwise for each step of the direction, with y pointing down
+    for (var i = 0u; i < direction; i++) {
+        corner = vec2(-corner.y, corner.x);
+    }
+
+    let world_pos = vec2<f32>(cell) + 0.5 + corner;
+    let screen_pos = (world_pos - camera.centre) / camera.zoom + camera.screen_resolution / 2.;
+
+    var result: VertexOutput;
+    result.position = vec4<f32>(
+        screen_pos.x / camera.screen_resolution.x * 2.0 - 1.0,
+        1.0 - screen_pos.y / camera.screen_resolution.y * 2.0,
+        0.0, 1.0
+    );
+    // Spread each ant's hue around by the golden ratio, so that neighbouring ants stand apart
+    let hue = fract(f32(ant) * 0.618034) * 6.2831853;
+    result.colour = 0.5 + 0.5 * cos(hue - vec3(0.0, 2.0943951, 4.1887902));
+    return result;
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(vertex.colour, 1.0);
+}
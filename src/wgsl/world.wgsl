@@ -0,0 +1,55 @@
+#define_import_path world
+
+// Helpers shared by every shader which samples the world.
+// Binding arrays of textures can't be declared here as naga fails to re-parse the generated header,
+// so only the storage buffer backend has its bindings in this module.
+
+#ifdef STORAGE_BUFFER_WORLD
+
+// Each chunk is a flat array of words, with `WORDS_PER_PIXEL` consecutive words making up a pixel
+@group(0) @binding(0) var<storage, read> chunk_0: array<u32>;
+#if CHUNK_COUNT > 1
+@group(0) @binding(1) var<storage, read> chunk_1: array<u32>;
+#endif
+#if CHUNK_COUNT > 2
+@group(0) @binding(2) var<storage, read> chunk_2: array<u32>;
+#endif
+#if CHUNK_COUNT > 3
+@group(0) @binding(3) var<storage, read> chunk_3: array<u32>;
+#endif
+
+fn load_word(chunk: u32, offset: u32) -> u32 {
+    switch chunk {
+        case 0u: { return chunk_0[offset]; }
+#if CHUNK_COUNT > 1
+        case 1u: { return chunk_1[offset]; }
+#endif
+#if CHUNK_COUNT > 2
+        case 2u: { return chunk_2[offset]; }
+#endif
+#if CHUNK_COUNT > 3
+        case 3u: { return chunk_3[offset]; }
+#endif
+        default: { return 0u; }
+    }
+}
+
+fn load_linear(index: u32) -> vec4u {
+    let chunk = index / #CHUNK_PIXELS;
+    let offset = (index % #CHUNK_PIXELS) * #WORDS_PER_PIXEL;
+
+    var pixel = vec4u(0u);
+    for (var w = 0u; w < #WORDS_PER_PIXEL; w++) {
+        pixel[w] = load_word(chunk, offset + w);
+    }
+    return pixel;
+}
+
+#endif
+fn in_bounds(pos: vec2i) -> bool {
+    return all(pos >= vec2i(0)) && all(pos < vec2i(#PIXELS_X, #PIXELS_Y));
+}
+
+fn linear_index(pos: vec2i) -> u32 {
+    return u32(pos.y) * #PIXELS_X + u32(pos.x);
+}
@@ -0,0 +1,627 @@
+use std::{collections::HashMap, num::NonZero, ops::Range, str::FromStr};
+
+use glam::{uvec2, UVec2};
+use naga_oil::compose::ShaderDefValue;
+
+use crate::{BITS_PER_WORD, WORKGROUP_SIZE};
+
+/// How cells are packed into a single texel of the world
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum CellPacking {
+    /// One `u32` per texel (`R32Uint`)
+    #[default]
+    Scalar,
+    /// Four `u32`s per texel (`Rgba32Uint`), so each invocation processes 128 cells
+    Vector,
+    /// One `f32` cell per texel (`R32Float`), for continuous states such as Lenia's
+    Float,
+}
+
+impl CellPacking {
+    pub const fn words_per_pixel(self) -> u32 {
+        match self {
+            CellPacking::Scalar | CellPacking::Float => 1,
+            CellPacking::Vector => 4,
+        }
+    }
+
+    pub const fn bits_per_pixel(self) -> u32 {
+        self.words_per_pixel() * BITS_PER_WORD
+    }
+
+    pub const fn texture_format(self) -> wgpu::TextureFormat {
+        match self {
+            CellPacking::Scalar => wgpu::TextureFormat::R32Uint,
+            CellPacking::Vector => wgpu::TextureFormat::Rgba32Uint,
+            CellPacking::Float => wgpu::TextureFormat::R32Float,
+        }
+    }
+
+    /// Size of a world of `world_size` cells, each `cell_bits` wide, in texels, rounding the width
+    /// up to whole texels
+    pub const fn texture_size(self, world_size: UVec2, cell_bits: u32) -> UVec2 {
+        uvec2((world_size.x * cell_bits).div_ceil(self.bits_per_pixel()), world_size.y)
+    }
+
+    pub const fn workgroup_dims(self, world_size: UVec2, cell_bits: u32) -> UVec2 {
+        let texture_size = self.texture_size(world_size, cell_bits);
+        uvec2(texture_size.x.div_ceil(WORKGROUP_SIZE.x), texture_size.y.div_ceil(WORKGROUP_SIZE.y))
+    }
+}
+
+/// Where the world lives on the GPU
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorldBackend {
+    /// Tiles of storage textures bound as a binding array
+    TextureArray,
+    /// Plain storage buffers with linear addressing, for adapters without binding arrays
+    StorageBuffer,
+}
+
+/// Maximum number of buffers the world can be split across with [`WorldBackend::StorageBuffer`]
+const MAX_BUFFER_CHUNKS: u32 = 4;
+
+impl WorldBackend {
+    pub const TEXTURE_ARRAY_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+        .union(wgpu::Features::TEXTURE_BINDING_ARRAY)
+        .union(wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY)
+        .union(wgpu::Features::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING);
+
+    /// Picks the fastest backend the given features allow
+    pub fn select(features: wgpu::Features) -> Self {
+        if features.contains(Self::TEXTURE_ARRAY_FEATURES) {
+            WorldBackend::TextureArray
+        } else {
+            WorldBackend::StorageBuffer
+        }
+    }
+
+    pub fn required_features(self) -> wgpu::Features {
+        match self {
+            WorldBackend::TextureArray => Self::TEXTURE_ARRAY_FEATURES,
+            WorldBackend::StorageBuffer => wgpu::Features::empty(),
+        }
+    }
+
+    /// Whether a world of `world_size` cells can be created within `limits`
+    pub fn fits(self, packing: CellPacking, world_size: UVec2, cell_bits: u32, limits: &wgpu::Limits) -> bool {
+        match self {
+            WorldBackend::TextureArray => true,
+            WorldBackend::StorageBuffer => {
+                let texture_size = packing.texture_size(world_size, cell_bits);
+                let chunk_rows = buffer_chunk_rows(packing, texture_size, limits);
+                chunk_rows > 0 && texture_size.y.div_ceil(chunk_rows) <= MAX_BUFFER_CHUNKS
+            }
+        }
+    }
+}
+
+impl FromStr for WorldBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "texture-array" => Ok(WorldBackend::TextureArray),
+            "storage-buffer" => Ok(WorldBackend::StorageBuffer),
+            _ => Err(format!("Unknown backend '{s}', expected texture-array or storage-buffer")),
+        }
+    }
+}
+
+/// Rows of texels which fit in a single storage buffer. Rows are kept whole so that each chunk is a
+/// horizontal band of the world
+fn buffer_chunk_rows(packing: CellPacking, texture_size: UVec2, limits: &wgpu::Limits) -> u32 {
+    let texel_bytes = packing.words_per_pixel() as u64 * size_of::<u32>() as u64;
+    let max_chunk_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+
+    (max_chunk_bytes / texel_bytes / texture_size.x as u64).min(texture_size.y as u64) as u32
+}
+
+/// A staging buffer holding part of the world, waiting to be copied into place
+struct Readback {
+    staging: wgpu::Buffer,
+    first_row: u32,
+    /// Where the part starts within a row, in words
+    column: usize,
+    width: usize,
+    /// Words between the starts of consecutive rows in the staging buffer
+    stride: usize,
+}
+
+enum WorldStorage {
+    Textures([Vec<wgpu::Texture>; 2]),
+    Buffers {
+        buffers: [Vec<wgpu::Buffer>; 2],
+        /// Number of texels held by each buffer (apart from the last)
+        chunk_pixels: u32,
+    },
+}
+
+/// Bits each cell takes to hold `states` states, a power of two so that cells never straddle words
+pub(crate) fn cell_bits(states: u32) -> u32 {
+    match states {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+pub struct World {
+    packing: CellPacking,
+    /// Bits holding each cell's state
+    cell_bits: u32,
+    /// Size in texels
+    texture_size: UVec2,
+    storage: WorldStorage,
+}
+
+impl World {
+    pub fn new(
+        packing: CellPacking,
+        cell_bits: u32,
+        world_size: UVec2,
+        backend: WorldBackend,
+        limits: &wgpu::Limits,
+        device: &wgpu::Device
+    ) -> Self {
+        assert!(BITS_PER_WORD.is_multiple_of(cell_bits), "Cells can't straddle words");
+        let texture_size = packing.texture_size(world_size, cell_bits);
+        let storage = match backend {
+            WorldBackend::TextureArray => Self::new_textures(packing, texture_size, limits.max_texture_dimension_2d, device),
+            WorldBackend::StorageBuffer => Self::new_buffers(packing, texture_size, limits, device),
+        };
+
+        Self {
+            packing,
+            cell_bits,
+            texture_size,
+            storage,
+        }
+    }
+
+    /// Size of the world in cells, which may be wider than asked for to fill whole texels
+    pub fn size(&self) -> UVec2 {
+        self.texture_size * uvec2(self.packing.bits_per_pixel() / self.cell_bits, 1)
+    }
+
+    pub fn cell_bits(&self) -> u32 {
+        self.cell_bits
+    }
+
+    pub fn packing(&self) -> CellPacking {
+        self.packing
+    }
+
+    /// Overwrites whole rows of texels in one copy of the world, starting at `first_row`.
+    /// `words` holds the rows back to back, in the same layout as the shaders use. Float cells are
+    /// the bits of each `f32`
+    pub fn write_rows(&self, queue: &wgpu::Queue, copy: usize, first_row: u32, words: &[u32]) {
+        let row_words = (self.texture_size.x * self.packing.words_per_pixel()) as usize;
+        assert!(words.len().is_multiple_of(row_words), "Only whole rows can be written");
+        let rows = (words.len() / row_words) as u32;
+        assert!(first_row + rows <= self.texture_size.y, "Rows are outside of the world");
+
+        match &self.storage {
+            WorldStorage::Textures(textures) => {
+                let tile_size = textures[copy][0].width().max(textures[copy][0].height());
+                let grid_width = self.texture_size.x.div_ceil(textures[copy][0].width());
+
+                for (i, tile) in textures[copy].iter().enumerate() {
+                    let origin = uvec2(i as u32 % grid_width, i as u32 / grid_width) * tile_size;
+                    let start = first_row.max(origin.y);
+                    let end = (first_row + rows).min(origin.y + tile.height());
+                    if start >= end {
+                        continue;
+                    }
+
+                    let tile_words = (tile.width() * self.packing.words_per_pixel()) as usize;
+                    let column = origin.x as usize * self.packing.words_per_pixel() as usize;
+                    let data = (start..end)
+                        .flat_map(|row| {
+                            let row_start = (row - first_row) as usize * row_words + column;
+                            &words[row_start..row_start + tile_words]
+                        })
+                        .copied()
+                        .collect::<Vec<_>>();
+
+                    queue.write_texture(
+                        wgpu::ImageCopyTexture {
+                            texture: tile,
+                            mip_level: 0,
+                            origin: wgpu::Origin3d { x: 0, y: start - origin.y, z: 0 },
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        bytemuck::cast_slice(&data),
+                        wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(tile_words as u32 * size_of::<u32>() as u32),
+                            rows_per_image: None,
+                        },
+                        wgpu::Extent3d { width: tile.width(), height: end - start, depth_or_array_layers: 1 },
+                    );
+                }
+            },
+            WorldStorage::Buffers { buffers, chunk_pixels } => {
+                // Chunks hold whole rows, so each row lands in a single buffer
+                let chunk_rows = chunk_pixels / self.texture_size.x;
+                for (i, row) in words.chunks_exact(row_words).enumerate() {
+                    let y = first_row + i as u32;
+                    let offset = (y % chunk_rows) as u64 * row_words as u64 * size_of::<u32>() as u64;
+                    queue.write_buffer(&buffers[copy][(y / chunk_rows) as usize], offset, bytemuck::cast_slice(row));
+                }
+            },
+        }
+    }
+
+    /// Reads whole rows of texels back from one copy of the world, in the same layout as
+    /// [`World::write_rows`] takes. Blocks until the GPU has finished
+    pub fn read_rows(&self, device: &wgpu::Device, queue: &wgpu::Queue, copy: usize, rows: Range<u32>) -> Vec<u32> {
+        assert!(rows.end <= self.texture_size.y, "Rows are outside of the world");
+        let words_per_pixel = self.packing.words_per_pixel();
+        let row_words = (self.texture_size.x * words_per_pixel) as usize;
+        let mut words = vec![0; rows.len() * row_words];
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder")
+        });
+        let mut readbacks = Vec::new();
+
+        match &self.storage {
+            WorldStorage::Textures(textures) => {
+                let tile_size = textures[copy][0].width().max(textures[copy][0].height());
+                let grid_width = self.texture_size.x.div_ceil(textures[copy][0].width());
+
+                for (i, tile) in textures[copy].iter().enumerate() {
+                    let origin = uvec2(i as u32 % grid_width, i as u32 / grid_width) * tile_size;
+                    let start = rows.start.max(origin.y);
+                    let end = rows.end.min(origin.y + tile.height());
+                    if start >= end {
+                        continue;
+                    }
+
+                    let width = tile.width() * words_per_pixel;
+                    let bytes_per_row = (width * size_of::<u32>() as u32).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+                    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("world_readback"),
+                        size: bytes_per_row as u64 * (end - start) as u64,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    });
+
+                    encoder.copy_texture_to_buffer(
+                        wgpu::ImageCopyTexture {
+                            texture: tile,
+                            mip_level: 0,
+                            origin: wgpu::Origin3d { x: 0, y: start - origin.y, z: 0 },
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        wgpu::ImageCopyBuffer {
+                            buffer: &staging,
+                            layout: wgpu::ImageDataLayout {
+                                offset: 0,
+                                bytes_per_row: Some(bytes_per_row),
+                                rows_per_image: None,
+                            },
+                        },
+                        wgpu::Extent3d { width: tile.width(), height: end - start, depth_or_array_layers: 1 },
+                    );
+
+                    readbacks.push(Readback {
+                        staging,
+                        first_row: start,
+                        column: (origin.x * words_per_pixel) as usize,
+                        width: width as usize,
+                        stride: bytes_per_row as usize / size_of::<u32>(),
+                    });
+                }
+            },
+            WorldStorage::Buffers { buffers, chunk_pixels } => {
+                let chunk_rows = chunk_pixels / self.texture_size.x;
+                let row_bytes = (row_words * size_of::<u32>()) as u64;
+
+                for (i, buffer) in buffers[copy].iter().enumerate() {
+                    let chunk_start = i as u32 * chunk_rows;
+                    let start = rows.start.max(chunk_start);
+                    let end = rows.end.min(chunk_start + chunk_rows);
+                    if start >= end {
+                        continue;
+                    }
+
+                    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("world_readback"),
+                        size: (end - start) as u64 * row_bytes,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    });
+                    encoder.copy_buffer_to_buffer(buffer, (start - chunk_start) as u64 * row_bytes, &staging, 0, staging.size());
+
+                    readbacks.push(Readback {
+                        staging,
+                        first_row: start,
+                        column: 0,
+                        width: row_words,
+                        stride: row_words,
+                    });
+                }
+            },
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+        for readback in &readbacks {
+            readback.staging
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, |result| result.expect("Failed to map world readback"));
+        }
+        device.poll(wgpu::Maintain::Wait);
+
+        for readback in readbacks {
+            let view = readback.staging.slice(..).get_mapped_range();
+            let data: &[u32] = bytemuck::cast_slice(&view);
+
+            for (i, row) in data.chunks_exact(readback.stride).enumerate() {
+                let start = (readback.first_row - rows.start) as usize * row_words + i * row_words + readback.column;
+                words[start..start + readback.width].copy_from_slice(&row[..readback.width]);
+            }
+        }
+
+        words
+    }
+
+    fn new_textures(packing: CellPacking, texture_size: UVec2, max_texture_size: u32, device: &wgpu::Device) -> WorldStorage {
+        let grid_dims = uvec2(texture_size.x.div_ceil(max_texture_size), texture_size.y.div_ceil(max_texture_size));
+        let tiles = grid_dims.element_product();
+
+        let gen_textures = || {
+            let mut textures = Vec::with_capacity(tiles as usize);
+
+            for y in 0..grid_dims.y {
+                for x in 0..grid_dims.x {
+                    let desc = wgpu::TextureDescriptor {
+                        label: Some("world_tile"),
+                        size: wgpu::Extent3d {
+                            width: (texture_size.x - x * max_texture_size).min(max_texture_size),
+                            height: (texture_size.y - y * max_texture_size).min(max_texture_size),
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: packing.texture_format(),
+                        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+                        view_formats: &[],
+                    };
+
+                    textures.push(device.create_texture(&desc));
+                }
+            }
+            textures
+        };
+
+        WorldStorage::Textures([gen_textures(), gen_textures()])
+    }
+
+    fn new_buffers(packing: CellPacking, texture_size: UVec2, limits: &wgpu::Limits, device: &wgpu::Device) -> WorldStorage {
+        let texel_bytes = packing.words_per_pixel() as u64 * size_of::<u32>() as u64;
+        let chunk_rows = buffer_chunk_rows(packing, texture_size, limits);
+        assert!(chunk_rows > 0, "Storage buffers are too small to hold a single row of the world");
+        let chunk_count = texture_size.y.div_ceil(chunk_rows);
+        assert!(
+            chunk_count <= MAX_BUFFER_CHUNKS,
+            "World needs {chunk_count} storage buffers but at most {MAX_BUFFER_CHUNKS} are supported"
+        );
+
+        let chunk_pixels = chunk_rows * texture_size.x;
+
+        let gen_buffers = || (0..chunk_count)
+            .map(|i| {
+                let rows = (texture_size.y - i * chunk_rows).min(chunk_rows);
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("world_chunk"),
+                    size: rows as u64 * texture_size.x as u64 * texel_bytes,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        WorldStorage::Buffers {
+            buffers: [gen_buffers(), gen_buffers()],
+            chunk_pixels,
+        }
+    }
+
+    /// Number of textures or buffers making up a single copy of the world
+    fn resource_count(&self) -> u32 {
+        match &self.storage {
+            WorldStorage::Textures(textures) => textures[0].len() as u32,
+            WorldStorage::Buffers { buffers, .. } => buffers[0].len() as u32,
+        }
+    }
+
+    fn read_entries(&self, binding: u32, visibility: wgpu::ShaderStages) -> Vec<wgpu::BindGroupLayoutEntry> {
+        match self.storage {
+            WorldStorage::Textures(_) => vec![wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::ReadOnly,
+                    format: self.packing.texture_format(),
+                    view_dimension: wgpu::TextureViewDimension::D2
+                },
+                count: NonZero::new(self.resource_count()),
+            }],
+            WorldStorage::Buffers { .. } => (0..self.resource_count())
+                .map(|i| wgpu::BindGroupLayoutEntry {
+                    binding: binding + i,
+                    visibility,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Entries for the copy being written, which can also be read back with `readable`. Storage
+    /// buffers can always be read, but only single word texels can be read from storage textures
+    fn write_entries(&self, binding: u32, visibility: wgpu::ShaderStages, readable: bool) -> Vec<wgpu::BindGroupLayoutEntry> {
+        match self.storage {
+            WorldStorage::Textures(_) => vec![wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: if readable { wgpu::StorageTextureAccess::ReadWrite } else { wgpu::StorageTextureAccess::WriteOnly },
+                    format: self.packing.texture_format(),
+                    view_dimension: wgpu::TextureViewDimension::D2
+                },
+                count: NonZero::new(self.resource_count()),
+            }],
+            WorldStorage::Buffers { .. } => (0..self.resource_count())
+                .map(|i| wgpu::BindGroupLayoutEntry {
+                    binding: binding + i,
+                    visibility,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Binding of the first output resource in the compute bind group
+    fn output_binding(&self) -> u32 {
+        match self.storage {
+            WorldStorage::Textures(_) => 1,
+            WorldStorage::Buffers { .. } => MAX_BUFFER_CHUNKS,
+        }
+    }
+
+    /// Creates bind groups from `grids`, where each entry holds the copies of the world bound
+    /// starting at the given bindings
+    fn bind_groups<const N: usize>(
+        &self,
+        layout: &wgpu::BindGroupLayout,
+        device: &wgpu::Device,
+        grids: [[(usize, u32); N]; 2]
+    ) -> [wgpu::BindGroup; 2] {
+        match &self.storage {
+            WorldStorage::Textures(textures) => {
+                let views = textures
+                    .iter()
+                    .map(|grid| grid.iter()
+                        .map(|texture| texture.create_view(&Default::default()))
+                        .collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+
+                let view_refs = views
+                    .iter()
+                    .map(|grid| grid
+                        .iter()
+                        .collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+
+                grids.map(|grid| device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout,
+                    entries: &grid.map(|(copy, binding)| wgpu::BindGroupEntry {
+                        binding,
+                        resource: wgpu::BindingResource::TextureViewArray(&view_refs[copy])
+                    }),
+                }))
+            },
+            WorldStorage::Buffers { buffers, .. } => grids.map(|grid| device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout,
+                entries: &grid
+                    .iter()
+                    .flat_map(|&(copy, binding)| buffers[copy]
+                        .iter()
+                        .enumerate()
+                        .map(move |(i, buffer)| wgpu::BindGroupEntry {
+                            binding: binding + i as u32,
+                            resource: buffer.as_entire_binding()
+                        }))
+                    .collect::<Vec<_>>(),
+            })),
+        }
+    }
+
+    pub fn fragment_bind_group_layout(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &self.read_entries(0, wgpu::ShaderStages::FRAGMENT)
+        })
+    }
+
+    pub fn fragment_bind_groups(&self, layout: &wgpu::BindGroupLayout, device: &wgpu::Device) -> [wgpu::BindGroup; 2] {
+        self.bind_groups(layout, device, [[(0, 0)], [(1, 0)]])
+    }
+
+    /// Layout binding one copy of the world to read and the other to write. With `read_output` the
+    /// copy being written can be read first, which still holds the generation before the current one
+    pub fn compute_bind_group_layout(&self, device: &wgpu::Device, read_output: bool) -> wgpu::BindGroupLayout {
+        let mut entries = self.read_entries(0, wgpu::ShaderStages::COMPUTE);
+        entries.extend(self.write_entries(self.output_binding(), wgpu::ShaderStages::COMPUTE, read_output));
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &entries
+        })
+    }
+
+    pub fn compute_bind_groups(&self, layout: &wgpu::BindGroupLayout, device: &wgpu::Device) -> [wgpu::BindGroup; 2] {
+        let output = self.output_binding();
+        self.bind_groups(layout, device, [[(0, 0), (1, output)], [(1, 0), (0, output)]])
+    }
+
+    pub fn shader_defs(&self) -> HashMap<String, ShaderDefValue> {
+        let texture_size = self.texture_size;
+
+        let mut defs = HashMap::from([
+            ("BITS_PER_PIXEL".into(), ShaderDefValue::UInt(self.packing.bits_per_pixel())),
+            ("WORDS_PER_PIXEL".into(), ShaderDefValue::UInt(self.packing.words_per_pixel())),
+            ("BITS_PER_CELL".into(), ShaderDefValue::UInt(self.cell_bits)),
+            ("PIXELS_X".into(), ShaderDefValue::UInt(texture_size.x)),
+            ("PIXELS_Y".into(), ShaderDefValue::UInt(texture_size.y)),
+        ]);
+
+        match self.packing {
+            CellPacking::Scalar => {},
+            CellPacking::Vector => {
+                defs.insert("VECTOR_PACKING".into(), ShaderDefValue::Bool(true));
+            },
+            CellPacking::Float => {
+                defs.insert("FLOAT_CELLS".into(), ShaderDefValue::Bool(true));
+            },
+        }
+
+        match &self.storage {
+            WorldStorage::Textures(textures) => {
+                let tile_width = textures[0][0].width();
+                let max_tile_dim = textures[0][0].height().max(tile_width);
+                defs.extend([
+                    ("TILE_SIZE".into(), ShaderDefValue::UInt(max_tile_dim)),
+                    ("GRID_WIDTH".into(), ShaderDefValue::UInt(texture_size.x.div_ceil(tile_width))),
+                    // naga_oil evaluates `#if`s even inside inactive blocks, so this must always be defined
+                    ("CHUNK_COUNT".into(), ShaderDefValue::UInt(0)),
+                ]);
+            },
+            WorldStorage::Buffers { buffers, chunk_pixels } => {
+                defs.extend([
+                    ("STORAGE_BUFFER_WORLD".into(), ShaderDefValue::Bool(true)),
+                    ("CHUNK_PIXELS".into(), ShaderDefValue::UInt(*chunk_pixels)),
+                    ("CHUNK_COUNT".into(), ShaderDefValue::UInt(buffers[0].len() as u32)),
+                ]);
+            },
+        }
+
+        defs
+    }
+}